@@ -1,7 +1,7 @@
 #![no_std]
 
 use soroban_sdk::{
-    contract, contractimpl, contracttype, symbol_short, token, Address, Env, String,
+    contract, contractimpl, contracttype, symbol_short, Address, Env, String, Vec,
 };
 
 // ---------------------------------------------------------------------------
@@ -20,6 +20,24 @@ pub enum DataKey {
     Balance(Address),
     /// Allowances: (owner, spender) → (amount, expiration_ledger).
     Allowance(Address, Address),
+    /// Spenders an owner has approved, pruned on revocation.
+    AllowanceSpenders(Address),
+    /// Accounts frozen by the admin; a frozen account cannot move funds.
+    Frozen(Address),
+    /// Time-locked (vesting) slices of an account's balance.
+    Locks(Address),
+    /// Number of accounts with a positive balance.
+    HolderCount,
+    /// When true, only allowlisted accounts may send or receive transfers.
+    TransferAllowlist,
+    /// Accounts cleared (e.g. KYC'd) to transfer while the allowlist is on.
+    TransferAllowed(Address),
+    /// Length in seconds of a mint throttle window.
+    MintWindowSecs,
+    /// Most BITE that may be minted per window; 0 means unlimited.
+    MaxMintPerWindow,
+    /// `(window index, amount minted in it)` for the current window.
+    WindowMinted,
 }
 
 // ---------------------------------------------------------------------------
@@ -50,6 +68,19 @@ pub struct AllowanceData {
     pub expiration_ledger: u32,
 }
 
+// ---------------------------------------------------------------------------
+// Vesting lock
+// ---------------------------------------------------------------------------
+
+/// A slice of an account's balance that cannot be moved before `unlock_at`.
+#[contracttype]
+#[derive(Clone)]
+pub struct VestingLock {
+    pub amount: i128,
+    /// Ledger timestamp from which the amount becomes transferable.
+    pub unlock_at: u64,
+}
+
 // ---------------------------------------------------------------------------
 // Contract
 // ---------------------------------------------------------------------------
@@ -63,29 +94,53 @@ impl LoyaltyToken {
     // Initialisation
     // -----------------------------------------------------------------------
 
-    /// Deploy the BITE token.
+    /// Deploy the BITE token with the default "Bite Rewards" / "BITE" /
+    /// 7-decimal metadata.
     ///
     /// # Arguments
     /// - `admin`   – address with mint authority.
     /// - `minter`  – optional secondary minter (pass `admin` to disable).
     pub fn initialize(env: Env, admin: Address, minter: Address) {
+        let name = String::from_str(&env, "Bite Rewards");
+        let symbol = String::from_str(&env, "BITE");
+        Self::initialize_with_meta(env, admin, minter, name, symbol, 7);
+    }
+
+    /// Deploy the token with custom metadata, e.g. to match a 6-decimal
+    /// accounting scheme.  `decimals` may not exceed 18.
+    pub fn initialize_with_meta(
+        env: Env,
+        admin: Address,
+        minter: Address,
+        name: String,
+        symbol: String,
+        decimals: u32,
+    ) {
         if env.storage().instance().has(&DataKey::Admin) {
             panic!("already initialized");
         }
+        if decimals > 18 {
+            panic!("decimals must be at most 18");
+        }
         env.storage().instance().set(&DataKey::Admin, &admin);
         env.storage().instance().set(&DataKey::Minter, &minter);
         env.storage().instance().set(&DataKey::TotalSupply, &0i128);
         env.storage().instance().set(
             &MetaKey::Meta,
             &TokenMeta {
-                name: String::from_str(&env, "Bite Rewards"),
-                symbol: String::from_str(&env, "BITE"),
-                decimals: 7,
+                name,
+                symbol,
+                decimals,
             },
         );
         env.storage().instance().extend_ttl(17_280, 17_280);
     }
 
+    /// Whether `initialize` has already been called.
+    pub fn is_initialized(env: Env) -> bool {
+        env.storage().instance().has(&DataKey::Admin)
+    }
+
     // -----------------------------------------------------------------------
     // Admin / Minter actions
     // -----------------------------------------------------------------------
@@ -95,29 +150,134 @@ impl LoyaltyToken {
         caller.require_auth();
         Self::assert_admin_or_minter(&env, &caller);
 
-        if amount <= 0 {
-            panic!("amount must be positive");
+        Self::do_mint(&env, &to, amount);
+    }
+
+    /// Mint to many accounts in one call.  Only callable by admin or minter.
+    ///
+    /// With `silent` set, the per-recipient `mint` events are replaced by a
+    /// single `mintbatch` event carrying `(count, total)`, which keeps large
+    /// batches under the per-transaction event limit.
+    pub fn mint_batch(env: Env, caller: Address, mints: Vec<(Address, i128)>, silent: bool) {
+        caller.require_auth();
+        Self::assert_admin_or_minter(&env, &caller);
+
+        let mut total: i128 = 0;
+        for (to, amount) in mints.iter() {
+            if silent {
+                Self::credit_mint(&env, &to, amount);
+            } else {
+                Self::do_mint(&env, &to, amount);
+            }
+            total += amount;
         }
 
-        let new_balance = Self::balance_of(&env, &to) + amount;
-        Self::set_balance(&env, &to, new_balance);
+        if silent {
+            env.events().publish(
+                (symbol_short!("mintbatch"), symbol_short!("BITE")),
+                (mints.len(), total),
+            );
+        }
+    }
 
-        let supply: i128 = env
-            .storage()
-            .instance()
-            .get(&DataKey::TotalSupply)
-            .unwrap_or(0);
+    /// Mint `amount` BITE to `to` that cannot be transferred or burned
+    /// before the ledger timestamp `unlock_at`.  Only callable by admin or
+    /// minter.
+    pub fn mint_locked(env: Env, caller: Address, to: Address, amount: i128, unlock_at: u64) {
+        caller.require_auth();
+        Self::assert_admin_or_minter(&env, &caller);
+
+        let now = env.ledger().timestamp();
+        if unlock_at <= now {
+            panic!("unlock time must be in the future");
+        }
+
+        Self::do_mint(&env, &to, amount);
+
+        let mut locks: Vec<VestingLock> = Vec::new(&env);
+        for lock in Self::locks_of(&env, &to).iter() {
+            // Drop locks that have already expired while we are rewriting.
+            if lock.unlock_at > now {
+                locks.push_back(lock);
+            }
+        }
+        locks.push_back(VestingLock { amount, unlock_at });
+
+        let ttl: u32 = 2_073_600;
         env.storage()
-            .instance()
-            .set(&DataKey::TotalSupply, &(supply + amount));
-        env.storage().instance().extend_ttl(17_280, 17_280);
+            .persistent()
+            .set(&DataKey::Locks(to.clone()), &locks);
+        env.storage()
+            .persistent()
+            .extend_ttl(&DataKey::Locks(to.clone()), ttl, ttl);
 
         env.events().publish(
-            (symbol_short!("mint"), symbol_short!("BITE")),
-            (to, amount),
+            (symbol_short!("lock"), symbol_short!("BITE")),
+            (to, amount, unlock_at),
         );
     }
 
+    /// Freeze `account` so it can no longer transfer or burn (admin only).
+    pub fn freeze(env: Env, caller: Address, account: Address) {
+        caller.require_auth();
+        Self::assert_admin_or_panic(&env, &caller);
+        Self::set_frozen(&env, &account, true);
+    }
+
+    /// Lift a freeze previously placed on `account` (admin only).
+    pub fn unfreeze(env: Env, caller: Address, account: Address) {
+        caller.require_auth();
+        Self::assert_admin_or_panic(&env, &caller);
+        Self::set_frozen(&env, &account, false);
+    }
+
+    /// Require both sides of a transfer to be allowlisted (admin only).
+    /// Minting is unaffected, so rewards still reach any account.
+    pub fn set_transfer_allowlist(env: Env, caller: Address, enabled: bool) {
+        caller.require_auth();
+        Self::assert_admin_or_panic(&env, &caller);
+        env.storage()
+            .instance()
+            .set(&DataKey::TransferAllowlist, &enabled);
+        env.storage().instance().extend_ttl(17_280, 17_280);
+    }
+
+    /// Add `account` to, or remove it from, the transfer allowlist (admin
+    /// only).
+    pub fn set_transfer_allowed(env: Env, caller: Address, account: Address, allowed: bool) {
+        caller.require_auth();
+        Self::assert_admin_or_panic(&env, &caller);
+        let key = DataKey::TransferAllowed(account);
+        if allowed {
+            let ttl: u32 = 2_073_600;
+            env.storage().persistent().set(&key, &true);
+            env.storage().persistent().extend_ttl(&key, ttl, ttl);
+        } else {
+            env.storage().persistent().remove(&key);
+        }
+    }
+
+    /// Throttle minting to at most `max_per_window` BITE per
+    /// `window_secs`-long window (admin only).  Windows are aligned to
+    /// `timestamp / window_secs`; a `max_per_window` of 0 lifts the limit.
+    pub fn set_mint_rate_limit(env: Env, caller: Address, window_secs: u64, max_per_window: i128) {
+        caller.require_auth();
+        Self::assert_admin_or_panic(&env, &caller);
+        if max_per_window < 0 {
+            panic!("limit must not be negative");
+        }
+        if max_per_window > 0 && window_secs == 0 {
+            panic!("window must be positive");
+        }
+        env.storage()
+            .instance()
+            .set(&DataKey::MintWindowSecs, &window_secs);
+        env.storage()
+            .instance()
+            .set(&DataKey::MaxMintPerWindow, &max_per_window);
+        env.storage().instance().extend_ttl(17_280, 17_280);
+    }
+
     /// Update the authorised minter address (admin only).
     pub fn set_minter(env: Env, caller: Address, new_minter: Address) {
         caller.require_auth();
@@ -143,6 +303,35 @@ impl LoyaltyToken {
         Self::balance_of(&env, &account)
     }
 
+    /// Return the portion of `account`'s balance that is still time-locked.
+    pub fn locked_balance(env: Env, account: Address) -> i128 {
+        Self::locked_of(&env, &account)
+    }
+
+    /// Return the amount `account` can actually move right now.
+    ///
+    /// This is `0` for a frozen account, otherwise `balance - locked_balance`.
+    /// Front ends should display this as the "available" figure.
+    pub fn spendable_balance(env: Env, account: Address) -> i128 {
+        Self::spendable_of(&env, &account)
+    }
+
+    /// Whether `account` may currently take part in transfers.  Always
+    /// true while the allowlist is disabled.
+    pub fn is_transfer_allowed(env: Env, account: Address) -> bool {
+        let enabled: bool = env
+            .storage()
+            .instance()
+            .get(&DataKey::TransferAllowlist)
+            .unwrap_or(false);
+        !enabled || env.storage().persistent().has(&DataKey::TransferAllowed(account))
+    }
+
+    /// Whether `account` is currently frozen.
+    pub fn is_frozen(env: Env, account: Address) -> bool {
+        Self::frozen_of(&env, &account)
+    }
+
     /// Transfer `amount` BITE from `from` to `to`.
     pub fn transfer(env: Env, from: Address, to: Address, amount: i128) {
         from.require_auth();
@@ -154,6 +343,18 @@ impl LoyaltyToken {
         Self::get_allowance(&env, &from, &spender)
     }
 
+    /// Spenders holding a live, non-zero allowance from `owner`; query
+    /// `allowance` for each to get the amount.
+    pub fn get_allowance_spenders(env: Env, owner: Address) -> Vec<Address> {
+        let mut active = Vec::new(&env);
+        for spender in Self::spenders_of(&env, &owner).iter() {
+            if Self::get_allowance(&env, &owner, &spender) > 0 {
+                active.push_back(spender);
+            }
+        }
+        active
+    }
+
     /// Approve `spender` to transfer up to `amount` on behalf of `from`.
     ///
     /// `expiration_ledger` is the last ledger at which the approval is valid.
@@ -187,6 +388,7 @@ impl LoyaltyToken {
                 ttl,
             );
         }
+        Self::update_spender_index(&env, &from, &spender, amount > 0);
         env.events().publish(
             (symbol_short!("approve"), symbol_short!("BITE")),
             (from, spender, amount, expiration_ledger),
@@ -230,6 +432,20 @@ impl LoyaltyToken {
         Self::do_burn(&env, &from, amount);
     }
 
+    /// Burn up to `amount` of `from`'s spendable BITE without their
+    /// signature, e.g. to reverse a reward for a refunded order.  Only
+    /// callable by admin or minter.  Locked or frozen BITE is never taken;
+    /// returns the amount actually burned.
+    pub fn clawback(env: Env, caller: Address, from: Address, amount: i128) -> i128 {
+        caller.require_auth();
+        Self::assert_admin_or_minter(&env, &caller);
+        let burned = amount.min(Self::spendable_of(&env, &from));
+        if burned > 0 {
+            Self::do_burn(&env, &from, burned);
+        }
+        burned.max(0)
+    }
+
     /// Burn `amount` BITE from `from` using a spender's allowance.
     pub fn burn_from(env: Env, spender: Address, from: Address, amount: i128) {
         spender.require_auth();
@@ -273,6 +489,14 @@ impl LoyaltyToken {
         meta.decimals
     }
 
+    /// Number of accounts currently holding a positive BITE balance.
+    pub fn holder_count(env: Env) -> u64 {
+        env.storage()
+            .instance()
+            .get(&DataKey::HolderCount)
+            .unwrap_or(0)
+    }
+
     pub fn total_supply(env: Env) -> i128 {
         env.storage()
             .instance()
@@ -280,10 +504,63 @@ impl LoyaltyToken {
             .unwrap_or(0)
     }
 
+    /// Amount minted so far in the current throttle window (0 when no
+    /// limit is set).
+    pub fn minted_in_window(env: Env) -> i128 {
+        match Self::current_window(&env) {
+            Some(window) => Self::window_minted(&env, window),
+            None => 0,
+        }
+    }
+
     // -----------------------------------------------------------------------
     // Private helpers
     // -----------------------------------------------------------------------
 
+    /// Index of the current throttle window, or `None` when minting is
+    /// unthrottled.
+    fn current_window(env: &Env) -> Option<u64> {
+        let max: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::MaxMintPerWindow)
+            .unwrap_or(0);
+        if max == 0 {
+            return None;
+        }
+        let secs: u64 = env.storage().instance().get(&DataKey::MintWindowSecs)?;
+        Some(env.ledger().timestamp() / secs)
+    }
+
+    fn window_minted(env: &Env, window: u64) -> i128 {
+        let minted: Option<(u64, i128)> = env.storage().instance().get(&DataKey::WindowMinted);
+        match minted {
+            Some((w, amount)) if w == window => amount,
+            _ => 0,
+        }
+    }
+
+    /// Count `amount` against the current window's mint limit, panicking
+    /// if it would be exceeded.
+    fn consume_mint_allowance(env: &Env, amount: i128) {
+        let window = match Self::current_window(env) {
+            Some(window) => window,
+            None => return,
+        };
+        let max: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::MaxMintPerWindow)
+            .unwrap_or(0);
+        let minted = Self::window_minted(env, window) + amount;
+        if minted > max {
+            panic!("mint limit for window exceeded");
+        }
+        env.storage()
+            .instance()
+            .set(&DataKey::WindowMinted, &(window, minted));
+    }
+
     fn balance_of(env: &Env, account: &Address) -> i128 {
         env.storage()
             .persistent()
@@ -292,6 +569,14 @@ impl LoyaltyToken {
     }
 
     fn set_balance(env: &Env, account: &Address, amount: i128) {
+        // Keep the holder count in step with zero ↔ positive transitions.
+        let previous = Self::balance_of(env, account);
+        if previous <= 0 && amount > 0 {
+            Self::adjust_holder_count(env, 1);
+        } else if previous > 0 && amount <= 0 {
+            Self::adjust_holder_count(env, -1);
+        }
+
         let ttl: u32 = 2_073_600;
         env.storage()
             .persistent()
@@ -301,14 +586,122 @@ impl LoyaltyToken {
             .extend_ttl(&DataKey::Balance(account.clone()), ttl, ttl);
     }
 
+    fn adjust_holder_count(env: &Env, delta: i64) {
+        let count = Self::holder_count(env.clone());
+        let count = if delta > 0 {
+            count + 1
+        } else {
+            count.saturating_sub(1)
+        };
+        env.storage().instance().set(&DataKey::HolderCount, &count);
+    }
+
+    fn frozen_of(env: &Env, account: &Address) -> bool {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Frozen(account.clone()))
+            .unwrap_or(false)
+    }
+
+    fn set_frozen(env: &Env, account: &Address, frozen: bool) {
+        let key = DataKey::Frozen(account.clone());
+        if frozen {
+            let ttl: u32 = 2_073_600;
+            env.storage().persistent().set(&key, &true);
+            env.storage().persistent().extend_ttl(&key, ttl, ttl);
+        } else {
+            env.storage().persistent().remove(&key);
+        }
+
+        env.events().publish(
+            (symbol_short!("freeze"), symbol_short!("BITE")),
+            (account.clone(), frozen),
+        );
+    }
+
+    fn locks_of(env: &Env, account: &Address) -> Vec<VestingLock> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Locks(account.clone()))
+            .unwrap_or_else(|| Vec::new(env))
+    }
+
+    fn locked_of(env: &Env, account: &Address) -> i128 {
+        let now = env.ledger().timestamp();
+        let mut locked: i128 = 0;
+        for lock in Self::locks_of(env, account).iter() {
+            if lock.unlock_at > now {
+                locked += lock.amount;
+            }
+        }
+        locked
+    }
+
+    fn spendable_of(env: &Env, account: &Address) -> i128 {
+        if Self::frozen_of(env, account) {
+            return 0;
+        }
+        let spendable = Self::balance_of(env, account) - Self::locked_of(env, account);
+        if spendable < 0 {
+            0
+        } else {
+            spendable
+        }
+    }
+
+    /// Ensure `from` holds `amount` and is allowed to move all of it.
+    fn assert_can_spend(env: &Env, from: &Address, amount: i128) {
+        if Self::frozen_of(env, from) {
+            panic!("account is frozen");
+        }
+        if Self::balance_of(env, from) < amount {
+            panic!("insufficient balance");
+        }
+        if Self::spendable_of(env, from) < amount {
+            panic!("balance is locked");
+        }
+    }
+
+    fn do_mint(env: &Env, to: &Address, amount: i128) {
+        Self::credit_mint(env, to, amount);
+        env.events().publish(
+            (symbol_short!("mint"), symbol_short!("BITE")),
+            (to.clone(), amount),
+        );
+    }
+
+    /// Mint without publishing an event.
+    fn credit_mint(env: &Env, to: &Address, amount: i128) {
+        if amount <= 0 {
+            panic!("amount must be positive");
+        }
+        Self::consume_mint_allowance(env, amount);
+
+        let new_balance = Self::balance_of(env, to) + amount;
+        Self::set_balance(env, to, new_balance);
+
+        let supply: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::TotalSupply)
+            .unwrap_or(0);
+        env.storage()
+            .instance()
+            .set(&DataKey::TotalSupply, &(supply + amount));
+        env.storage().instance().extend_ttl(17_280, 17_280);
+    }
+
     fn do_transfer(env: &Env, from: &Address, to: &Address, amount: i128) {
         if amount <= 0 {
             panic!("transfer amount must be positive");
         }
-        let from_bal = Self::balance_of(env, from);
-        if from_bal < amount {
-            panic!("insufficient balance");
+        if !Self::is_transfer_allowed(env.clone(), from.clone())
+            || !Self::is_transfer_allowed(env.clone(), to.clone())
+        {
+            panic!("transfer not allowed");
         }
+        Self::assert_can_spend(env, from, amount);
+        let from_bal = Self::balance_of(env, from);
         Self::set_balance(env, from, from_bal - amount);
         Self::set_balance(env, to, Self::balance_of(env, to) + amount);
 
@@ -322,10 +715,8 @@ impl LoyaltyToken {
         if amount <= 0 {
             panic!("burn amount must be positive");
         }
+        Self::assert_can_spend(env, from, amount);
         let bal = Self::balance_of(env, from);
-        if bal < amount {
-            panic!("insufficient balance");
-        }
         Self::set_balance(env, from, bal - amount);
 
         let supply: i128 = env
@@ -362,6 +753,37 @@ impl LoyaltyToken {
         }
     }
 
+    fn spenders_of(env: &Env, owner: &Address) -> Vec<Address> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::AllowanceSpenders(owner.clone()))
+            .unwrap_or_else(|| Vec::new(env))
+    }
+
+    /// Add `spender` to (or drop it from) `owner`'s spender index.  Spenders
+    /// whose allowance has lapsed are dropped at the same time, which keeps
+    /// the index bounded by the owner's live approvals.
+    fn update_spender_index(env: &Env, owner: &Address, spender: &Address, active: bool) {
+        let mut spenders = Vec::new(env);
+        for existing in Self::spenders_of(env, owner).iter() {
+            if existing != *spender && Self::get_allowance(env, owner, &existing) > 0 {
+                spenders.push_back(existing);
+            }
+        }
+        if active {
+            spenders.push_back(spender.clone());
+        }
+
+        let key = DataKey::AllowanceSpenders(owner.clone());
+        if spenders.is_empty() {
+            env.storage().persistent().remove(&key);
+        } else {
+            let ttl: u32 = 2_073_600;
+            env.storage().persistent().set(&key, &spenders);
+            env.storage().persistent().extend_ttl(&key, ttl, ttl);
+        }
+    }
+
     fn assert_admin_or_panic(env: &Env, caller: &Address) {
         let admin: Address = env.storage().instance().get(&DataKey::Admin).unwrap();
         if caller != &admin {
@@ -385,8 +807,8 @@ impl LoyaltyToken {
 #[cfg(test)]
 mod test {
     use super::*;
-    use soroban_sdk::testutils::Address as _;
-    use soroban_sdk::Env;
+    use soroban_sdk::testutils::{Address as _, Events, Ledger};
+    use soroban_sdk::{Env, IntoVal};
 
     fn setup() -> (Env, LoyaltyTokenClient<'static>, Address) {
         let env = Env::default();
@@ -477,4 +899,247 @@ mod test {
         let rando = Address::generate(&env);
         client.mint(&rando, &rando, &1_000_000);
     }
+
+    #[test]
+    fn test_spendable_balance_with_lock_and_freeze() {
+        let (env, client, admin) = setup();
+        let user = Address::generate(&env);
+        let unlock_at = env.ledger().timestamp() + 3_600;
+
+        client.mint(&admin, &user, &400_000);
+        client.mint_locked(&admin, &user, &600_000, &unlock_at);
+        assert_eq!(client.balance(&user), 1_000_000);
+        assert_eq!(client.locked_balance(&user), 600_000);
+        assert_eq!(client.spendable_balance(&user), 400_000);
+
+        client.freeze(&admin, &user);
+        assert_eq!(client.spendable_balance(&user), 0);
+
+        client.unfreeze(&admin, &user);
+        assert_eq!(client.spendable_balance(&user), 400_000);
+
+        // Once the lock expires the full balance becomes spendable.
+        env.ledger().with_mut(|l| l.timestamp = unlock_at);
+        assert_eq!(client.locked_balance(&user), 0);
+        assert_eq!(client.spendable_balance(&user), 1_000_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "balance is locked")]
+    fn test_transfer_locked_balance_panics() {
+        let (env, client, admin) = setup();
+        let alice = Address::generate(&env);
+        let bob = Address::generate(&env);
+        let unlock_at = env.ledger().timestamp() + 3_600;
+
+        client.mint(&admin, &alice, &100_000);
+        client.mint_locked(&admin, &alice, &500_000, &unlock_at);
+        client.transfer(&alice, &bob, &200_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "account is frozen")]
+    fn test_frozen_account_cannot_transfer() {
+        let (env, client, admin) = setup();
+        let alice = Address::generate(&env);
+        let bob = Address::generate(&env);
+
+        client.mint(&admin, &alice, &100_000);
+        client.freeze(&admin, &alice);
+        client.transfer(&alice, &bob, &50_000);
+    }
+
+    #[test]
+    fn test_is_initialized() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let cid = env.register_contract(None, LoyaltyToken);
+        let client = LoyaltyTokenClient::new(&env, &cid);
+        let admin = Address::generate(&env);
+
+        assert!(!client.is_initialized());
+        client.initialize(&admin, &admin);
+        assert!(client.is_initialized());
+    }
+
+    #[test]
+    fn test_initialize_with_custom_metadata() {
+        let env = Env::default();
+        env.mock_all_auths();
+        let cid = env.register_contract(None, LoyaltyToken);
+        let client = LoyaltyTokenClient::new(&env, &cid);
+        let admin = Address::generate(&env);
+        client.initialize_with_meta(
+            &admin,
+            &admin,
+            &String::from_str(&env, "Crumb Points"),
+            &String::from_str(&env, "CRUMB"),
+            &6,
+        );
+        assert_eq!(client.name(), String::from_str(&env, "Crumb Points"));
+        assert_eq!(client.symbol(), String::from_str(&env, "CRUMB"));
+        assert_eq!(client.decimals(), 6u32);
+    }
+
+    #[test]
+    #[should_panic(expected = "decimals must be at most 18")]
+    fn test_initialize_rejects_excess_decimals() {
+        let env = Env::default();
+        let cid = env.register_contract(None, LoyaltyToken);
+        let client = LoyaltyTokenClient::new(&env, &cid);
+        let admin = Address::generate(&env);
+        let name = String::from_str(&env, "Crumb Points");
+        let symbol = String::from_str(&env, "CRUMB");
+        client.initialize_with_meta(&admin, &admin, &name, &symbol, &19);
+    }
+
+    #[test]
+    fn test_silent_mint_batch_emits_one_summary_event() {
+        let (env, client, admin) = setup();
+        let mut mints = Vec::new(&env);
+        for _ in 0..5 {
+            mints.push_back((Address::generate(&env), 1_000_000i128));
+        }
+
+        let before = env.events().all().len();
+        client.mint_batch(&admin, &mints, &true);
+        let events = env.events().all();
+        assert_eq!(events.len() - before, 1);
+
+        let (_, topics, data) = events.last().unwrap();
+        assert_eq!(
+            topics,
+            (symbol_short!("mintbatch"), symbol_short!("BITE")).into_val(&env)
+        );
+        let (count, total): (u32, i128) = data.into_val(&env);
+        assert_eq!((count, total), (5, 5_000_000));
+        assert_eq!(client.total_supply(), 5_000_000);
+    }
+
+    #[test]
+    fn test_mint_batch_emits_per_item_events() {
+        let (env, client, admin) = setup();
+        let mut mints = Vec::new(&env);
+        for _ in 0..3 {
+            mints.push_back((Address::generate(&env), 1_000_000i128));
+        }
+
+        let before = env.events().all().len();
+        client.mint_batch(&admin, &mints, &false);
+        assert_eq!(env.events().all().len() - before, 3);
+    }
+
+    #[test]
+    fn test_holder_count_tracks_zero_transitions() {
+        let (env, client, admin) = setup();
+        let alice = Address::generate(&env);
+        let bob = Address::generate(&env);
+        assert_eq!(client.holder_count(), 0);
+
+        client.mint(&admin, &alice, &1_000_000);
+        assert_eq!(client.holder_count(), 1);
+
+        // A partial transfer to a new account adds a holder; alice remains one.
+        client.transfer(&alice, &bob, &400_000);
+        assert_eq!(client.holder_count(), 2);
+
+        // Topping up an existing holder changes nothing.
+        client.transfer(&alice, &bob, &100_000);
+        assert_eq!(client.holder_count(), 2);
+
+        // Draining alice to zero removes that holder.
+        client.transfer(&alice, &bob, &500_000);
+        assert_eq!(client.holder_count(), 1);
+
+        client.burn(&bob, &1_000_000);
+        assert_eq!(client.holder_count(), 0);
+    }
+
+    #[test]
+    fn test_clawback_capped_at_spendable() {
+        let (env, client, admin) = setup();
+        let user = Address::generate(&env);
+        let outsider = Address::generate(&env);
+        let unlock_at = env.ledger().timestamp() + 3_600;
+        client.mint(&admin, &user, &400_000);
+        client.mint_locked(&admin, &user, &600_000, &unlock_at);
+
+        assert_eq!(client.clawback(&admin, &user, &100_000), 100_000);
+        assert_eq!(client.clawback(&admin, &user, &500_000), 300_000);
+        assert_eq!(client.balance(&user), 600_000);
+        assert_eq!(client.total_supply(), 600_000);
+        assert_eq!(client.clawback(&admin, &user, &1), 0);
+        assert!(client.try_clawback(&outsider, &user, &1).is_err());
+    }
+
+    #[test]
+    fn test_transfer_allowlist() {
+        let (env, client, admin) = setup();
+        let alice = Address::generate(&env);
+        let bob = Address::generate(&env);
+        client.set_transfer_allowlist(&admin, &true);
+
+        // Rewards can still be minted to anyone.
+        client.mint(&admin, &alice, &1_000);
+        assert!(client.try_transfer(&alice, &bob, &100).is_err());
+
+        client.set_transfer_allowed(&admin, &alice, &true);
+        assert!(client.try_transfer(&alice, &bob, &100).is_err());
+
+        client.set_transfer_allowed(&admin, &bob, &true);
+        client.transfer(&alice, &bob, &100);
+        assert_eq!(client.balance(&bob), 100);
+
+        client.set_transfer_allowlist(&admin, &false);
+        client.set_transfer_allowed(&admin, &bob, &false);
+        client.transfer(&alice, &bob, &100);
+        assert_eq!(client.balance(&bob), 200);
+    }
+
+    #[test]
+    fn test_mint_rate_limit_per_window() {
+        let (env, client, admin) = setup();
+        let user = Address::generate(&env);
+        env.ledger().with_mut(|l| l.timestamp = 10_000);
+        client.set_mint_rate_limit(&admin, &3_600, &1_000);
+
+        client.mint(&admin, &user, &600);
+        client.mint(&admin, &user, &400);
+        assert_eq!(client.minted_in_window(), 1_000);
+        assert!(client.try_mint(&admin, &user, &1).is_err());
+
+        // The next window starts from zero.
+        env.ledger().with_mut(|l| l.timestamp += 3_600);
+        assert_eq!(client.minted_in_window(), 0);
+        client.mint(&admin, &user, &1_000);
+        assert_eq!(client.balance(&user), 2_000);
+
+        // A zero limit lifts the throttle.
+        client.set_mint_rate_limit(&admin, &3_600, &0);
+        client.mint(&admin, &user, &5_000);
+        assert_eq!(client.balance(&user), 7_000);
+    }
+
+    #[test]
+    fn test_allowance_spenders_listed_and_pruned() {
+        let (env, client, _admin) = setup();
+        let alice = Address::generate(&env);
+        let bob = Address::generate(&env);
+        let carol = Address::generate(&env);
+        let expiry = env.ledger().sequence() + 1_000;
+
+        client.approve(&alice, &bob, &300_000, &expiry);
+        client.approve(&alice, &carol, &100_000, &expiry);
+        // Re-approving does not duplicate the entry.
+        client.approve(&alice, &bob, &400_000, &expiry);
+        let spenders = client.get_allowance_spenders(&alice);
+        assert_eq!(spenders.len(), 2);
+        assert_eq!(spenders.get(0), Some(carol.clone()));
+        assert_eq!(spenders.get(1), Some(bob));
+
+        // Revoking removes the spender.
+        client.approve(&alice, &carol, &0, &0);
+        assert_eq!(client.get_allowance_spenders(&alice).len(), 1);
+        assert!(!client.get_allowance_spenders(&alice).contains(&carol));
+    }
 }
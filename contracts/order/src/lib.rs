@@ -6,30 +6,114 @@
 //!
 //! ## Order lifecycle
 //! ```text
-//! Pending ──► Confirmed ──► Preparing ──► Ready ──► Delivered
+//! Pending ──► Confirmed ──► Preparing ──► Ready ──► Delivered ──► Closed
 //!    │              │
 //!    └──────────────┴──────────────────────────────► Cancelled
 //! ```
 //!
+//! A delivered, undisputed order is closed by the admin once the inspection
+//! window has passed; `Closed` is the final bookkeeping state.  Payment
+//! settlement is tracked by the Payment contract and is not checked here.
+//!
 //! ## Roles
 //! - **Admin** – contract deployer; full control.
 //! - **Restaurant owner** – confirms, updates, and marks orders as ready/delivered
 //!   for orders belonging to their restaurant.
-//! - **Customer** – places an order; can cancel while it is still `Pending`.
+//! - **Customer** – places an order; can cancel while it is still `Pending`
+//!   (or in another admin-configured status), or within the configured grace
+//!   window after it is `Confirmed`.
+//!
+//! ## Events
+//! Every event payload is a `(seq, data)` pair.  `seq` increases by one per
+//! event across the whole contract, so an indexer that stores the last `seq`
+//! it processed can resume and detect gaps; see `current_event_seq`.
+//! Delivery additionally emits a single `completed` event carrying
+//! `(order_id, customer, total, reward)` after any reward events.
+//!
+//! ## Rewards
+//! When a loyalty token is configured, delivering an order mints BITE to the
+//! customer at the admin-set reward rate.  This contract must be the token's
+//! minter for that call to succeed; if the mint fails the delivery still
+//! goes through and the admin can later `grant_missed_reward`.  In claim
+//! mode rewards accrue instead
+//! and are minted when the customer calls `claim_rewards`.  With a promo
+//! lock configured, rewards minted directly during a promo are time-locked
+//! in the token via `mint_locked`.  Resolving a dispute with
+//! `refund_dispute` claws the order's reward back.
 
 #![no_std]
 
 use soroban_sdk::{
-    contract, contractimpl, contracttype, symbol_short, vec, Address, Env, String, Vec,
+    contract, contractclient, contractimpl, contracttype, symbol_short, vec, Address, Env,
+    IntoVal, String, Symbol, Val, Vec,
 };
 
+/// Upper bound on the number of IDs returned by multi-list views.
+const MAX_RESULTS: u32 = 200;
+
+/// Upper bound on the number of orders loaded by a filtering scan.
+const MAX_SCAN: u32 = 500;
+
+/// Maximum number of entries kept in an order's status timeline.
+const MAX_TIMELINE: u32 = 20;
+
+/// Longest notes (in bytes) that can be scanned for control characters.
+const MAX_NOTES_SCAN: u32 = 1_024;
+
+/// Maximum number of buckets in an order value histogram.
+const MAX_HISTOGRAM_BUCKETS: u32 = 50;
+
+/// Default TTL (in ledgers) for persistent entries, roughly 120 days.
+const DEFAULT_PERSISTENT_TTL: u32 = 2_073_600;
+
+/// Default TTL (in ledgers) for instance storage, roughly one day.
+const DEFAULT_INSTANCE_TTL: u32 = 17_280;
+
+/// Smallest TTL the admin may configure, one day of ledgers.
+const MIN_TTL: u32 = 17_280;
+
+// ---------------------------------------------------------------------------
+// External contracts
+// ---------------------------------------------------------------------------
+
+/// The subset of the BITE loyalty token used for minting rewards.
+#[contractclient(name = "LoyaltyClient")]
+pub trait LoyaltyTokenInterface {
+    fn mint(env: Env, caller: Address, to: Address, amount: i128);
+    fn mint_locked(env: Env, caller: Address, to: Address, amount: i128, unlock_at: u64);
+    fn balance(env: Env, account: Address) -> i128;
+    fn clawback(env: Env, caller: Address, from: Address, amount: i128) -> i128;
+}
+
+/// The subset of the Restaurant Registry used to vet new orders and
+/// restaurant owners.
+#[contractclient(name = "RegistryClient")]
+pub trait RegistryInterface {
+    fn is_accepting_orders(env: Env, restaurant_id: u64) -> bool;
+    fn get_owner_restaurant(env: Env, owner: Address) -> u64;
+}
+
+/// The subset of the Payment contract used to escrow at order time.
+#[contractclient(name = "PaymentClient")]
+pub trait PaymentInterface {
+    fn escrow_for_order(
+        env: Env,
+        order_contract: Address,
+        payer: Address,
+        order_id: u64,
+        restaurant_wallet: Address,
+        token_address: Address,
+        amount: i128,
+    );
+}
+
 // ---------------------------------------------------------------------------
 // Types
 // ---------------------------------------------------------------------------
 
 /// Lifecycle state of an order.
 #[contracttype]
-#[derive(Clone, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum OrderStatus {
     Pending,
     Confirmed,
@@ -37,6 +121,104 @@ pub enum OrderStatus {
     Ready,
     Delivered,
     Cancelled,
+    /// Delivered and past the inspection window; final.
+    Closed,
+}
+
+/// Structured reason recorded when an order is cancelled.
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub enum CancelReason {
+    /// The customer changed their mind.
+    CustomerChanged,
+    /// The restaurant declined the order.
+    RestaurantRejected,
+    /// One or more items are unavailable.
+    OutOfStock,
+    /// The order was not handled in time.
+    Timeout,
+    /// Cancelled as the outcome of a dispute.
+    Dispute,
+    /// Anything else, including cancellations without a stated reason.
+    Other,
+}
+
+/// How fractional reward amounts are rounded to whole BITE base units.
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub enum RoundMode {
+    /// Truncate toward zero (default).
+    Floor,
+    /// Round half up.
+    Nearest,
+    /// Round any fraction up.
+    Ceil,
+}
+
+/// Structural rules applied to plaintext order notes.
+#[contracttype]
+#[derive(Clone)]
+pub struct NotesPolicy {
+    /// Maximum length of the notes in bytes (0 = no limit).
+    pub max_len: u32,
+    /// Reject ASCII control characters other than tab, LF and CR.
+    pub reject_control_chars: bool,
+}
+
+/// A time-boxed reward multiplier, e.g. "double BITE this weekend".
+#[contracttype]
+#[derive(Clone)]
+pub struct Promo {
+    /// Multiplier applied to the rate-based reward (20 000 = 2×).
+    pub multiplier_bps: u32,
+    /// First ledger timestamp at which the promo applies.
+    pub start: u64,
+    /// Ledger timestamp at which the promo stops applying (exclusive).
+    pub end: u64,
+}
+
+/// Per-restaurant order counters, maintained as orders change status.
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct RestaurantStats {
+    /// Orders ever placed with the restaurant.
+    pub order_count: u32,
+    /// Orders neither delivered nor cancelled.
+    pub active_orders: u32,
+    /// Orders delivered (including those since closed).
+    pub delivered_orders: u32,
+    /// Sum of `total_amount` over delivered orders, in stroops.
+    pub total_revenue: i128,
+}
+
+/// Platform-wide order totals for the admin dashboard.
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct GlobalSummary {
+    /// Orders ever placed.
+    pub total_orders: u64,
+    /// Orders currently delivered (or closed after delivery).
+    pub total_delivered: u64,
+    /// Orders currently cancelled.
+    pub total_cancelled: u64,
+    /// Sum of `total_amount` over delivered orders, in stroops.
+    pub total_volume: i128,
+}
+
+/// Per-customer aggregates behind `get_customer_stats`.
+#[contracttype]
+#[derive(Clone, Debug, PartialEq)]
+pub struct CustomerStats {
+    /// Orders ever placed by the customer.
+    pub order_count: u32,
+    /// Sum of `total_amount` over the customer's delivered orders.
+    pub total_spent: i128,
+    /// Restaurant the customer has placed the most orders with (0 = none).
+    pub favorite_restaurant: u64,
+    /// Number of orders placed with `favorite_restaurant`.
+    pub favorite_orders: u32,
+    /// BITE rewarded for the customer's orders, net of clawbacks.
+    pub bite_earned: i128,
 }
 
 /// A single line-item in an order.
@@ -66,8 +248,22 @@ pub struct Order {
     pub status: OrderStatus,
     pub created_at: u64,
     pub updated_at: u64,
+    /// Ledger timestamp at which the order was confirmed (0 until then).
+    pub confirmed_at: u64,
+    /// Ledger timestamp at which the order was delivered (0 until then).
+    pub delivered_at: u64,
     /// Optional delivery/special instructions.
     pub notes: String,
+    /// Set by the customer to freeze the lifecycle until an admin reviews it.
+    pub disputed: bool,
+    /// When true, `notes` is empty and the real notes are encrypted
+    /// off-chain; clients must resolve and decrypt `notes_ref`.
+    pub notes_encrypted: bool,
+    /// Reference to the encrypted notes (empty for plaintext orders).
+    pub notes_ref: String,
+    /// Set once the delivery reward has been minted (or accrued in claim
+    /// mode, or absorbed by reward debt) for this order.
+    pub reward_minted: bool,
 }
 
 // ---------------------------------------------------------------------------
@@ -83,6 +279,87 @@ pub enum DataKey {
     RestaurantOrders(u64),
     /// Ordered list of order IDs for a customer.
     CustomerOrders(Address),
+    /// BITE loyalty token address; rewards are disabled while unset.
+    LoyaltyToken,
+    /// Reward in BITE base units per 10 000 stroops of order total.
+    RewardRateBps,
+    /// Minimum seconds between two rewards for the same customer.
+    RewardCooldown,
+    /// Rounding applied to fractional rewards.
+    RoundMode,
+    /// Whether secondary (analytic) events are emitted.  Defaults to true.
+    VerboseEvents,
+    /// Extra BITE minted on a customer's first delivered order.
+    FirstOrderBonus,
+    /// Number of a customer's deliveries evaluated for a reward (i.e. made
+    /// while a loyalty token was configured).
+    CustomerDelivered(Address),
+    /// Ledger timestamp of a customer's most recent reward.
+    LastReward(Address),
+    /// Current menu price for (restaurant ID, menu item ID), in stroops.
+    MenuPrice(u64, u64),
+    /// When true, `place_order` rejects items whose price differs from the menu.
+    StrictPricing,
+    /// Seconds after confirmation during which the customer may still cancel.
+    CancelGraceSecs,
+    /// Statuses in which a customer may always cancel (defaults to `Pending`).
+    CustomerCancellable,
+    /// Optional promotional reward multiplier.
+    Promo,
+    /// Optional structural rules for order notes.
+    NotesPolicy,
+    /// Seconds that rewards minted during a promo stay locked (0 = liquid).
+    PromoLockSecs,
+    /// When true, delivery rewards accrue until the customer claims them.
+    ClaimMode,
+    /// Rewards accrued but not yet claimed, per customer.
+    PendingReward(Address),
+    /// Reward rate (bps) overriding `RewardRateBps` for a restaurant ID.
+    RestaurantRewardRate(u64),
+    /// Singleton: highest rate a restaurant override may use (default 0).
+    MaxRestaurantRewardBps,
+    /// BITE minted or accrued for an order, kept for clawback.
+    OrderReward(u64),
+    /// Clawed-back BITE a customer could not cover, netted off future
+    /// rewards.
+    RewardDebt(Address),
+    /// Sequence number of the last event published by this contract.
+    EventSeq,
+    /// Seconds after delivery before an order may be closed.
+    InspectionWindowSecs,
+    /// TTL (in ledgers) applied when extending persistent entries.
+    PersistentTtl,
+    /// TTL (in ledgers) applied when extending instance storage.
+    InstanceTtl,
+    /// Payment contract used by `place_order_and_escrow`.
+    PaymentContract,
+    /// Restaurant Registry consulted before accepting new orders.
+    RegistryContract,
+    /// Singleton: platform-wide counters behind `get_global_summary`.
+    GlobalSummary,
+    /// Aggregate counters per restaurant ID.
+    RestaurantStats(u64),
+    /// Seconds after placement before a pending order may be auto-confirmed,
+    /// per restaurant ID (0 or absent = disabled).
+    AutoConfirmSecs(u64),
+    /// Running aggregates per customer.
+    CustomerStats(Address),
+    /// Orders placed by a customer with one restaurant ID.
+    CustomerRestaurantCount(Address, u64),
+    /// Order ID → CancelReason for cancelled orders.
+    CancelReason(u64),
+    /// Customers a restaurant refuses orders from.
+    Blocked(u64, Address),
+    /// `(from, to)` status changes the admin may make with `set_status`.
+    AllowedTransition(OrderStatus, OrderStatus),
+    /// `(status, timestamp)` history of an order, oldest first.
+    OrderTimeline(u64),
+    /// Number of multi-restaurant carts checked out so far.
+    CartCount,
+    /// Order IDs created together by one `place_multi_order` call.
+    Cart(u64),
+    /// Cart ID an order was placed under, if any.
+    OrderCart(u64),
 }
 
 // ---------------------------------------------------------------------------
@@ -105,7 +382,12 @@ impl OrderContract {
         }
         env.storage().instance().set(&DataKey::Admin, &admin);
         env.storage().instance().set(&DataKey::Count, &0u64);
-        env.storage().instance().extend_ttl(17_280, 17_280);
+        Self::extend_instance(&env);
+    }
+
+    /// Whether `initialize` has already been called.
+    pub fn is_initialized(env: Env) -> bool {
+        env.storage().instance().has(&DataKey::Admin)
     }
 
     // -----------------------------------------------------------------------
@@ -130,82 +412,140 @@ impl OrderContract {
         notes: String,
     ) -> u64 {
         customer.require_auth();
+        Self::create_order(&env, customer, restaurant_id, items, notes, None)
+    }
 
-        if items.is_empty() {
-            panic!("order must contain at least one item");
+    /// Place an order whose delivery notes are encrypted off-chain.
+    ///
+    /// Only `notes_ref` (e.g. a ciphertext hash or storage pointer) is kept
+    /// on-chain; the order's plaintext `notes` stay empty and
+    /// `notes_encrypted` tells clients to fetch and decrypt the reference.
+    pub fn place_order_encrypted(
+        env: Env,
+        customer: Address,
+        restaurant_id: u64,
+        items: Vec<OrderItem>,
+        notes_ref: String,
+    ) -> u64 {
+        customer.require_auth();
+        if notes_ref.is_empty() {
+            panic!("notes reference required");
         }
+        let notes = String::from_str(&env, "");
+        Self::create_order(&env, customer, restaurant_id, items, notes, Some(notes_ref))
+    }
 
-        // Compute total from items.
-        let mut total: i128 = 0;
-        for item in items.iter() {
-            if item.quantity == 0 {
-                panic!("quantity must be greater than zero");
-            }
-            if item.unit_price <= 0 {
-                panic!("unit price must be positive");
-            }
-            total += item.unit_price * item.quantity as i128;
-        }
+    /// Place an order and escrow its total in one transaction.
+    ///
+    /// The customer must first approve the Payment contract to spend at
+    /// least the order total of `token`; the Payment contract then pulls
+    /// the funds with `transfer_from`.  Either both records are written or
+    /// neither is.
+    pub fn place_order_and_escrow(
+        env: Env,
+        customer: Address,
+        restaurant_id: u64,
+        items: Vec<OrderItem>,
+        notes: String,
+        restaurant_wallet: Address,
+        token: Address,
+    ) -> u64 {
+        customer.require_auth();
 
-        let count: u64 = env
+        let payment: Address = env
             .storage()
             .instance()
-            .get(&DataKey::Count)
-            .unwrap_or(0);
-        let id: u64 = count + 1;
-        let now = env.ledger().timestamp();
+            .get(&DataKey::PaymentContract)
+            .unwrap_or_else(|| panic!("payment contract not set"));
+        let id = Self::create_order(&env, customer.clone(), restaurant_id, items, notes, None);
+        let total = Self::load_order(&env, id).total_amount;
 
-        let order = Order {
-            id,
-            restaurant_id,
-            customer: customer.clone(),
-            items: items.clone(),
-            total_amount: total,
-            status: OrderStatus::Pending,
-            created_at: now,
-            updated_at: now,
-            notes,
-        };
+        PaymentClient::new(&env, &payment).escrow_for_order(
+            &env.current_contract_address(),
+            &customer,
+            &id,
+            &restaurant_wallet,
+            &token,
+            &total,
+        );
+        id
+    }
 
-        let ttl: u32 = 2_073_600;
+    /// Check out a cart spanning several restaurants in one call.
+    ///
+    /// Each `(restaurant_id, items, notes)` entry becomes its own order,
+    /// validated exactly as `place_order` would.  The orders are linked by
+    /// a shared cart ID (see `get_cart_orders` / `get_order_cart`) and a
+    /// single `multiplaced` event.  Returns the new order IDs in input
+    /// order.
+    pub fn place_multi_order(
+        env: Env,
+        customer: Address,
+        orders: Vec<(u64, Vec<OrderItem>, String)>,
+    ) -> Vec<u64> {
+        customer.require_auth();
+        if orders.is_empty() {
+            panic!("cart must contain at least one order");
+        }
+
+        let cart_id: u64 = env
+            .storage()
+            .instance()
+            .get(&DataKey::CartCount)
+            .unwrap_or(0)
+            + 1;
+        env.storage().instance().set(&DataKey::CartCount, &cart_id);
+
+        let ttl = Self::persistent_ttl(&env);
+        let mut ids: Vec<u64> = Vec::new(&env);
+        for (restaurant_id, items, notes) in orders.iter() {
+            let id = Self::create_order(&env, customer.clone(), restaurant_id, items, notes, None);
+            env.storage()
+                .persistent()
+                .set(&DataKey::OrderCart(id), &cart_id);
+            env.storage()
+                .persistent()
+                .extend_ttl(&DataKey::OrderCart(id), ttl, ttl);
+            ids.push_back(id);
+        }
         env.storage()
             .persistent()
-            .set(&DataKey::Order(id), &order);
+            .set(&DataKey::Cart(cart_id), &ids);
         env.storage()
             .persistent()
-            .extend_ttl(&DataKey::Order(id), ttl, ttl);
+            .extend_ttl(&DataKey::Cart(cart_id), ttl, ttl);
 
-        // Append to restaurant index.
-        Self::append_to_list(
-            &env,
-            DataKey::RestaurantOrders(restaurant_id),
-            id,
-            ttl,
-        );
-        // Append to customer index.
-        Self::append_to_list(
+        Self::emit(
             &env,
-            DataKey::CustomerOrders(customer.clone()),
-            id,
-            ttl,
-        );
-
-        env.storage().instance().set(&DataKey::Count, &id);
-        env.storage().instance().extend_ttl(17_280, 17_280);
-
-        env.events().publish(
-            (symbol_short!("placed"), symbol_short!("order")),
-            (id, restaurant_id, customer, total),
+            Symbol::new(&env, "multiplaced"),
+            (cart_id, customer, ids.clone()),
         );
-
-        id
+        ids
     }
 
     /// Cancel an order.
     ///
-    /// - Customers may cancel while the order is `Pending`.
+    /// - Customers may cancel while the order is in one of the configured
+    ///   `CustomerCancellable` statuses (`Pending` by default), or while it
+    ///   is `Confirmed` and still inside the `CancelGraceSecs` window.  When
+    ///   a grace window is set it also limits configured statuses reached
+    ///   after confirmation.
     /// - The admin may cancel at any time (for dispute resolution).
+    ///
+    /// The reason is recorded as `CancelReason::Other`; use
+    /// `cancel_order_with_reason` to give one.
     pub fn cancel_order(env: Env, caller: Address, order_id: u64) {
+        Self::cancel_order_with_reason(env, caller, order_id, CancelReason::Other);
+    }
+
+    /// Cancel an order under the same rules as `cancel_order`, recording
+    /// `reason` on the order and in the `cancelled` event.
+    pub fn cancel_order_with_reason(
+        env: Env,
+        caller: Address,
+        order_id: u64,
+        reason: CancelReason,
+    ) {
         caller.require_auth();
 
         let mut order = Self::load_order(&env, order_id);
@@ -218,7 +558,7 @@ impl OrderContract {
             panic!("unauthorized");
         }
 
-        if order.status == OrderStatus::Delivered {
+        if order.status == OrderStatus::Delivered || order.status == OrderStatus::Closed {
             panic!("cannot cancel a delivered order");
         }
 
@@ -226,25 +566,137 @@ impl OrderContract {
             panic!("order already cancelled");
         }
 
-        if is_customer && order.status != OrderStatus::Pending {
-            panic!("customers may only cancel pending orders");
+        if is_customer && !is_admin && !Self::customer_may_cancel(&env, &order) {
+            panic!("order can no longer be cancelled by the customer");
         }
 
+        let previous = order.status.clone();
         order.status = OrderStatus::Cancelled;
         order.updated_at = env.ledger().timestamp();
         Self::save_order(&env, &order);
+        Self::track_status_change(&env, &order, previous);
+        Self::set_cancel_reason(&env, order_id, Some(reason.clone()));
+
+        Self::emit(&env, symbol_short!("cancelled"), (order_id, caller, reason));
+    }
+
+    /// Flag an order as disputed (customer only).
+    ///
+    /// While the flag is set the order cannot be advanced, so the kitchen
+    /// cannot mark it delivered until an admin calls `clear_dispute`.
+    /// Delivered orders may also be disputed, which blocks `close_order`
+    /// until the admin clears the dispute or resolves it with
+    /// `refund_dispute`.
+    pub fn flag_dispute(env: Env, customer: Address, order_id: u64) {
+        customer.require_auth();
+
+        let mut order = Self::load_order(&env, order_id);
+        if customer != order.customer {
+            panic!("unauthorized");
+        }
+        if !Self::is_open(&order.status) && order.status != OrderStatus::Delivered {
+            panic!("cannot dispute a finalized order");
+        }
+        if order.disputed {
+            panic!("order already disputed");
+        }
+
+        order.disputed = true;
+        order.updated_at = env.ledger().timestamp();
+        Self::save_order(&env, &order);
+
+        Self::emit(&env, symbol_short!("disputed"), (order_id, customer));
+    }
+
+    /// Fold one pending order into another (customer only).
+    ///
+    /// Both orders must be `Pending` and belong to the same customer and
+    /// restaurant.  `from_order_id`'s items are appended to `into_order_id`,
+    /// whose total is recomputed, and `from_order_id` is cancelled.
+    pub fn merge_orders(env: Env, customer: Address, into_order_id: u64, from_order_id: u64) {
+        customer.require_auth();
+
+        if into_order_id == from_order_id {
+            panic!("cannot merge an order into itself");
+        }
+        let mut into = Self::load_order(&env, into_order_id);
+        let mut from = Self::load_order(&env, from_order_id);
+        if customer != into.customer || customer != from.customer {
+            panic!("unauthorized");
+        }
+        if into.status != OrderStatus::Pending || from.status != OrderStatus::Pending {
+            panic!("only pending orders can be merged");
+        }
+        if into.restaurant_id != from.restaurant_id {
+            panic!("orders belong to different restaurants");
+        }
+
+        let now = env.ledger().timestamp();
+        into.items.append(&from.items);
+        into.total_amount += from.total_amount;
+        into.updated_at = now;
+        Self::save_order(&env, &into);
+
+        from.status = OrderStatus::Cancelled;
+        from.updated_at = now;
+        Self::save_order(&env, &from);
+        Self::track_status_change(&env, &from, OrderStatus::Pending);
+        Self::set_cancel_reason(&env, from_order_id, Some(CancelReason::CustomerChanged));
+
+        Self::emit(&env, symbol_short!("merged"), (into_order_id, from_order_id));
+    }
+
+    /// Mint all rewards accrued in claim mode to the customer.
+    ///
+    /// Returns the amount minted.
+    pub fn claim_rewards(env: Env, customer: Address) -> i128 {
+        customer.require_auth();
+
+        let pending_key = DataKey::PendingReward(customer.clone());
+        let amount: i128 = env.storage().persistent().get(&pending_key).unwrap_or(0);
+        if amount <= 0 {
+            panic!("no rewards to claim");
+        }
+        let token: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::LoyaltyToken)
+            .unwrap_or_else(|| panic!("loyalty token not set"));
 
-        env.events().publish(
-            (symbol_short!("cancelled"), symbol_short!("order")),
-            (order_id, caller),
+        env.storage().persistent().remove(&pending_key);
+        LoyaltyClient::new(&env, &token).mint(
+            &env.current_contract_address(),
+            &customer,
+            &amount,
         );
+
+        Self::emit(&env, symbol_short!("claimed"), (customer, amount));
+        amount
     }
 
-    
     // -----------------------------------------------------------------------
     // Restaurant / Admin actions
     // -----------------------------------------------------------------------
 
+    /// Create a `Pending` order on behalf of `customer`, e.g. for phone or
+    /// walk-in orders taken by staff.
+    ///
+    /// `caller` authorises the order instead of the customer and must be
+    /// the admin or the restaurant's owner according to the registry.  The
+    /// order is otherwise identical to one placed with `place_order`.
+    pub fn create_order_for_customer(
+        env: Env,
+        caller: Address,
+        customer: Address,
+        restaurant_id: u64,
+        items: Vec<OrderItem>,
+        notes: String,
+    ) -> u64 {
+        caller.require_auth();
+        Self::assert_admin_or_owner(&env, &caller, restaurant_id);
+        Self::create_order(&env, customer, restaurant_id, items, notes, None)
+    }
+
     /// Advance the order to the next status in the lifecycle.
     ///
     /// Only the contract admin may call this; in production you would add a
@@ -257,7 +709,11 @@ impl OrderContract {
         Self::assert_admin_or_panic(&env, &caller);
 
         let mut order = Self::load_order(&env, order_id);
+        if order.disputed {
+            panic!("order disputed");
+        }
 
+        let previous = order.status.clone();
         order.status = match order.status {
             OrderStatus::Pending => OrderStatus::Confirmed,
             OrderStatus::Confirmed => OrderStatus::Preparing,
@@ -265,214 +721,3358 @@ impl OrderContract {
             OrderStatus::Ready => OrderStatus::Delivered,
             OrderStatus::Delivered => panic!("order already delivered"),
             OrderStatus::Cancelled => panic!("cannot advance a cancelled order"),
+            OrderStatus::Closed => panic!("order already closed"),
         };
         order.updated_at = env.ledger().timestamp();
+        if order.status == OrderStatus::Confirmed {
+            order.confirmed_at = order.updated_at;
+        }
+        if order.status == OrderStatus::Delivered {
+            order.delivered_at = order.updated_at;
+        }
         Self::save_order(&env, &order);
+        Self::track_status_change(&env, &order, previous);
 
-        env.events().publish(
-            (symbol_short!("advanced"), symbol_short!("order")),
-            order_id,
-        );
+        Self::emit(&env, symbol_short!("advanced"), order_id);
+
+        if order.status == OrderStatus::Delivered {
+            let reward = Self::maybe_mint_reward(&env, &order);
+            if reward.is_some() {
+                order.reward_minted = true;
+                Self::save_order(&env, &order);
+            }
+            let reward = reward.unwrap_or(0);
+            let data = (order_id, order.customer.clone(), order.total_amount, reward);
+            Self::emit(&env, symbol_short!("completed"), data);
+        }
     }
 
-    /// Directly set an order's status (admin only – for dispute resolution).
-    pub fn set_status(env: Env, caller: Address, order_id: u64, status: OrderStatus) {
+    /// Confirm a still-`Pending` order once its restaurant's auto-confirm
+    /// window has elapsed since placement.
+    ///
+    /// Permissionless so a keeper can sweep stale orders; does nothing
+    /// beyond what the restaurant would have done by confirming.
+    pub fn auto_confirm(env: Env, order_id: u64) {
+        let mut order = Self::load_order(&env, order_id);
+        let window = Self::get_auto_confirm(env.clone(), order.restaurant_id);
+        if window == 0 {
+            panic!("auto-confirm disabled");
+        }
+        if order.status != OrderStatus::Pending {
+            panic!("order is not pending");
+        }
+        if order.disputed {
+            panic!("order disputed");
+        }
+        let now = env.ledger().timestamp();
+        if now < order.created_at.saturating_add(window) {
+            panic!("auto-confirm window not reached");
+        }
+
+        order.status = OrderStatus::Confirmed;
+        order.updated_at = now;
+        order.confirmed_at = now;
+        Self::save_order(&env, &order);
+        Self::track_status_change(&env, &order, OrderStatus::Pending);
+
+        Self::emit(&env, Symbol::new(&env, "autoconfirm"), order_id);
+    }
+
+    /// Clear a customer's dispute flag so the order can progress (admin only).
+    pub fn clear_dispute(env: Env, caller: Address, order_id: u64) {
         caller.require_auth();
         Self::assert_admin_or_panic(&env, &caller);
 
         let mut order = Self::load_order(&env, order_id);
-        order.status = status;
+        if !order.disputed {
+            panic!("order is not disputed");
+        }
+
+        order.disputed = false;
         order.updated_at = env.ledger().timestamp();
         Self::save_order(&env, &order);
 
-        env.events().publish(
-            (symbol_short!("setstatus"), symbol_short!("order")),
-            order_id,
-        );
+        Self::emit(&env, symbol_short!("undispute"), order_id);
     }
 
-    // -----------------------------------------------------------------------
-    // View functions
-    // -----------------------------------------------------------------------
+    /// Resolve a dispute in the customer's favour (admin only).
+    ///
+    /// The order is cancelled and any BITE it earned is clawed back: first
+    /// from the customer's unclaimed rewards, then by burning their
+    /// spendable balance.  Whatever cannot be recovered is recorded as
+    /// reward debt and deducted from future rewards.  Refunding the payment
+    /// itself is done on the Payment contract.
+    pub fn refund_dispute(env: Env, caller: Address, order_id: u64) {
+        caller.require_auth();
+        Self::assert_admin_or_panic(&env, &caller);
 
-    /// Fetch a single order by ID.
-    pub fn get_order(env: Env, order_id: u64) -> Order {
-        Self::load_order(&env, order_id)
-    }
+        let mut order = Self::load_order(&env, order_id);
+        if !order.disputed {
+            panic!("order is not disputed");
+        }
 
-    /// Return a list of order IDs for a restaurant.
-    pub fn get_restaurant_orders(env: Env, restaurant_id: u64) -> Vec<u64> {
-        env.storage()
-            .persistent()
-            .get(&DataKey::RestaurantOrders(restaurant_id))
-            .unwrap_or_else(|| vec![&env])
-    }
+        let previous = order.status.clone();
+        order.disputed = false;
+        order.status = OrderStatus::Cancelled;
+        order.updated_at = env.ledger().timestamp();
+        Self::save_order(&env, &order);
+        Self::track_status_change(&env, &order, previous);
 
-    /// Return a list of order IDs for a customer.
-    pub fn get_customer_orders(env: Env, customer: Address) -> Vec<u64> {
-        env.storage()
-            .persistent()
-            .get(&DataKey::CustomerOrders(customer))
-            .unwrap_or_else(|| vec![&env])
-    }
+        Self::set_cancel_reason(&env, order_id, Some(CancelReason::Dispute));
 
-    /// Total orders ever placed.
-    pub fn get_count(env: Env) -> u64 {
-        env.storage().instance().get(&DataKey::Count).unwrap_or(0)
+        let data = (order_id, caller, CancelReason::Dispute);
+        Self::emit(&env, symbol_short!("cancelled"), data);
+        Self::clawback_reward(&env, &order);
     }
 
-    // -----------------------------------------------------------------------
-    // Private helpers
-    // -----------------------------------------------------------------------
+    /// Grant the reward a delivered order missed, e.g. because no loyalty
+    /// token was configured, the customer was in cooldown or the mint
+    /// failed at delivery (admin only).
+    ///
+    /// The reward is recomputed at current rates, without the first-order
+    /// bonus, and ignores the cooldown.  Each order can be rewarded at
+    /// most once.  Returns the amount granted, `0` if reward debt absorbed
+    /// it.
+    pub fn grant_missed_reward(env: Env, caller: Address, order_id: u64) -> i128 {
+        caller.require_auth();
+        Self::assert_admin_or_panic(&env, &caller);
 
-    fn load_order(env: &Env, order_id: u64) -> Order {
-        env.storage()
-            .persistent()
-            .get(&DataKey::Order(order_id))
-            .unwrap_or_else(|| panic!("order not found"))
-    }
+        let mut order = Self::load_order(&env, order_id);
+        if order.status != OrderStatus::Delivered {
+            panic!("order not delivered");
+        }
+        if order.reward_minted {
+            panic!("reward already granted");
+        }
+        let token: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::LoyaltyToken)
+            .unwrap_or_else(|| panic!("loyalty token not set"));
 
-    fn save_order(env: &Env, order: &Order) {
-        let ttl: u32 = 2_073_600;
-        env.storage()
+        let amount = Self::reward_amount(&env, &order, false);
+        if amount <= 0 {
+            panic!("no reward to grant");
+        }
+        let granted = Self::mint_reward(&env, &token, &order, amount)
+            .unwrap_or_else(|| panic!("reward mint failed"));
+        order.reward_minted = true;
+        Self::save_order(&env, &order);
+
+        Self::emit(&env, symbol_short!("granted"), (order_id, order.customer, granted));
+        granted
+    }
+
+    /// Move an order to another restaurant, e.g. a different branch of a
+    /// cloud kitchen (admin only).
+    ///
+    /// Only allowed while the order is `Pending` or `Confirmed`.  The new
+    /// restaurant is checked as for a new order, and both restaurants'
+    /// order indexes and counters, and the customer's favourite-restaurant
+    /// counts, are updated.
+    pub fn reassign_order(env: Env, caller: Address, order_id: u64, new_restaurant_id: u64) {
+        caller.require_auth();
+        Self::assert_admin_or_panic(&env, &caller);
+
+        let mut order = Self::load_order(&env, order_id);
+        if !matches!(order.status, OrderStatus::Pending | OrderStatus::Confirmed) {
+            panic!("order can no longer be reassigned");
+        }
+        if order.disputed {
+            panic!("order disputed");
+        }
+        let old_restaurant_id = order.restaurant_id;
+        if new_restaurant_id == old_restaurant_id {
+            panic!("order already at this restaurant");
+        }
+        if Self::is_blocked(env.clone(), new_restaurant_id, order.customer.clone()) {
+            panic!("customer blocked");
+        }
+        Self::validate_new_order(&env, new_restaurant_id, &order.items);
+
+        let ttl = Self::persistent_ttl(&env);
+        let old_key = DataKey::RestaurantOrders(old_restaurant_id);
+        let mut old_orders = Self::get_restaurant_orders(env.clone(), old_restaurant_id);
+        if let Some(index) = old_orders.first_index_of(order_id) {
+            old_orders.remove(index);
+        }
+        env.storage().persistent().set(&old_key, &old_orders);
+        env.storage().persistent().extend_ttl(&old_key, ttl, ttl);
+        Self::append_to_list(
+            &env,
+            DataKey::RestaurantOrders(new_restaurant_id),
+            order_id,
+            ttl,
+        );
+
+        let mut old_stats = Self::get_restaurant_stats(env.clone(), old_restaurant_id);
+        old_stats.order_count = old_stats.order_count.saturating_sub(1);
+        old_stats.active_orders = old_stats.active_orders.saturating_sub(1);
+        Self::save_restaurant_stats(&env, old_restaurant_id, &old_stats);
+        let mut new_stats = Self::get_restaurant_stats(env.clone(), new_restaurant_id);
+        new_stats.order_count += 1;
+        new_stats.active_orders += 1;
+        Self::save_restaurant_stats(&env, new_restaurant_id, &new_stats);
+        Self::untrack_customer_order(&env, &order.customer, old_restaurant_id);
+        Self::track_customer_order(&env, &order.customer, new_restaurant_id, ttl);
+
+        order.restaurant_id = new_restaurant_id;
+        order.updated_at = env.ledger().timestamp();
+        Self::save_order(&env, &order);
+
+        let data = (order_id, old_restaurant_id, new_restaurant_id);
+        Self::emit(&env, Symbol::new(&env, "reassigned"), data);
+    }
+
+    /// Directly set an order's status (admin only), e.g. for dispute
+    /// resolution or custom flows such as skipping `Preparing`.
+    ///
+    /// Only transitions enabled with `set_allowed_transition` are accepted;
+    /// `advance_status` keeps following the default linear lifecycle.
+    /// Delivery carries reward side effects, so orders can neither be moved
+    /// to nor out of `Delivered` here; use `advance_status` and
+    /// `refund_dispute` instead.
+    pub fn set_status(env: Env, caller: Address, order_id: u64, status: OrderStatus) {
+        caller.require_auth();
+        Self::assert_admin_or_panic(&env, &caller);
+
+        if status == OrderStatus::Closed {
+            panic!("use close_order to close an order");
+        }
+        if status == OrderStatus::Delivered {
+            panic!("use advance_status to deliver an order");
+        }
+
+        let mut order = Self::load_order(&env, order_id);
+        if order.status == OrderStatus::Closed {
+            panic!("order is closed");
+        }
+        if order.status == OrderStatus::Delivered {
+            panic!("order already delivered");
+        }
+        let previous = order.status.clone();
+        if !Self::is_transition_allowed(env.clone(), previous.clone(), status.clone()) {
+            panic!("transition not allowed");
+        }
+        order.status = status;
+        order.updated_at = env.ledger().timestamp();
+        if order.status == OrderStatus::Confirmed {
+            order.confirmed_at = order.updated_at;
+        }
+        Self::save_order(&env, &order);
+        Self::track_status_change(&env, &order, previous);
+        let reason = (order.status == OrderStatus::Cancelled).then_some(CancelReason::Other);
+        Self::set_cancel_reason(&env, order_id, reason);
+
+        Self::emit(&env, symbol_short!("setstatus"), order_id);
+    }
+
+    /// Move a delivered order to its final `Closed` state (admin only).
+    ///
+    /// Only allowed once `InspectionWindowSecs` have passed since delivery.
+    /// Closing is pure bookkeeping: it neither checks payment settlement nor
+    /// mints rewards, and a closed order can no longer change status.
+    pub fn close_order(env: Env, caller: Address, order_id: u64) {
+        caller.require_auth();
+        Self::assert_admin_or_panic(&env, &caller);
+
+        let mut order = Self::load_order(&env, order_id);
+        if order.status != OrderStatus::Delivered {
+            panic!("only delivered orders can be closed");
+        }
+        if order.disputed {
+            panic!("order disputed");
+        }
+        let window: u64 = env
+            .storage()
+            .instance()
+            .get(&DataKey::InspectionWindowSecs)
+            .unwrap_or(0);
+        let now = env.ledger().timestamp();
+        if now < order.delivered_at.saturating_add(window) {
+            panic!("inspection window still open");
+        }
+
+        let previous = order.status.clone();
+        order.status = OrderStatus::Closed;
+        order.updated_at = now;
+        Self::save_order(&env, &order);
+        Self::track_status_change(&env, &order, previous);
+
+        Self::emit(&env, symbol_short!("closed"), order_id);
+    }
+
+    // -----------------------------------------------------------------------
+    // Order policy (admin)
+    // -----------------------------------------------------------------------
+
+    /// Set how long after delivery an order must wait before it can be
+    /// closed.
+    pub fn set_inspection_window(env: Env, caller: Address, window_secs: u64) {
+        caller.require_auth();
+        Self::assert_admin_or_panic(&env, &caller);
+        env.storage()
+            .instance()
+            .set(&DataKey::InspectionWindowSecs, &window_secs);
+        Self::extend_instance(&env);
+    }
+
+    /// Block or unblock `customer` from ordering at a restaurant.  Callable
+    /// by the admin or the restaurant's owner.
+    pub fn set_blocked(
+        env: Env,
+        caller: Address,
+        restaurant_id: u64,
+        customer: Address,
+        blocked: bool,
+    ) {
+        caller.require_auth();
+        Self::assert_admin_or_owner(&env, &caller, restaurant_id);
+        let key = DataKey::Blocked(restaurant_id, customer);
+        if blocked {
+            let ttl = Self::persistent_ttl(&env);
+            env.storage().persistent().set(&key, &true);
+            env.storage().persistent().extend_ttl(&key, ttl, ttl);
+        } else {
+            env.storage().persistent().remove(&key);
+        }
+    }
+
+    /// Allow or disallow moving orders from `from` to `to` with
+    /// `set_status` (admin only).
+    pub fn set_allowed_transition(
+        env: Env,
+        caller: Address,
+        from: OrderStatus,
+        to: OrderStatus,
+        allowed: bool,
+    ) {
+        caller.require_auth();
+        Self::assert_admin_or_panic(&env, &caller);
+        let key = DataKey::AllowedTransition(from, to);
+        if allowed {
+            let ttl = Self::persistent_ttl(&env);
+            env.storage().persistent().set(&key, &true);
+            env.storage().persistent().extend_ttl(&key, ttl, ttl);
+        } else {
+            env.storage().persistent().remove(&key);
+        }
+    }
+
+    /// Set how long a restaurant's orders may stay `Pending` before anyone
+    /// can `auto_confirm` them.  Callable by the admin or the restaurant's
+    /// owner; `0` disables auto-confirmation.
+    pub fn set_auto_confirm(env: Env, caller: Address, restaurant_id: u64, window_secs: u64) {
+        caller.require_auth();
+        Self::assert_admin_or_owner(&env, &caller, restaurant_id);
+        let key = DataKey::AutoConfirmSecs(restaurant_id);
+        let ttl = Self::persistent_ttl(&env);
+        env.storage().persistent().set(&key, &window_secs);
+        env.storage().persistent().extend_ttl(&key, ttl, ttl);
+    }
+
+    /// Set how long after confirmation a customer may still cancel.
+    /// Pass `0` to restrict customer cancellation to `Pending` orders.
+    pub fn set_cancel_grace(env: Env, caller: Address, grace_secs: u64) {
+        caller.require_auth();
+        Self::assert_admin_or_panic(&env, &caller);
+        env.storage()
+            .instance()
+            .set(&DataKey::CancelGraceSecs, &grace_secs);
+        Self::extend_instance(&env);
+    }
+
+    /// Set the TTLs (in ledgers) used when extending persistent entries and
+    /// instance storage.  Both must lie between one day of ledgers and the
+    /// network's maximum entry TTL.
+    pub fn set_storage_ttl(env: Env, caller: Address, persistent_ttl: u32, instance_ttl: u32) {
+        caller.require_auth();
+        Self::assert_admin_or_panic(&env, &caller);
+        let max = env.storage().max_ttl();
+        for ttl in [persistent_ttl, instance_ttl] {
+            if !(MIN_TTL..=max).contains(&ttl) {
+                panic!("ttl out of range");
+            }
+        }
+        env.storage()
+            .instance()
+            .set(&DataKey::PersistentTtl, &persistent_ttl);
+        env.storage()
+            .instance()
+            .set(&DataKey::InstanceTtl, &instance_ttl);
+        Self::extend_instance(&env);
+    }
+
+    /// Set the statuses in which customers may cancel their own orders,
+    /// e.g. `[Pending, Confirmed]`.  `Delivered`, `Cancelled` and `Closed`
+    /// are rejected.  When a grace window is set, cancelling a confirmed
+    /// order in any of these statuses is still limited to that window.
+    pub fn set_customer_cancellable(env: Env, caller: Address, statuses: Vec<OrderStatus>) {
+        caller.require_auth();
+        Self::assert_admin_or_panic(&env, &caller);
+        for status in statuses.iter() {
+            if !Self::is_open(&status) {
+                panic!("status cannot be cancelled");
+            }
+        }
+        env.storage()
+            .instance()
+            .set(&DataKey::CustomerCancellable, &statuses);
+        Self::extend_instance(&env);
+    }
+
+    /// Set the Payment contract used by `place_order_and_escrow`.  That
+    /// contract must in turn register this one as its Order contract.
+    pub fn set_payment_contract(env: Env, caller: Address, payment: Address) {
+        caller.require_auth();
+        Self::assert_admin_or_panic(&env, &caller);
+        env.storage()
+            .instance()
+            .set(&DataKey::PaymentContract, &payment);
+        Self::extend_instance(&env);
+    }
+
+    /// Set the Restaurant Registry (admin only).  Once set, orders for
+    /// restaurants that are inactive or awaiting approval are rejected.
+    pub fn set_registry_contract(env: Env, caller: Address, registry: Address) {
+        caller.require_auth();
+        Self::assert_admin_or_panic(&env, &caller);
+        env.storage()
+            .instance()
+            .set(&DataKey::RegistryContract, &registry);
+        Self::extend_instance(&env);
+    }
+
+    /// Enable or disable secondary events (reward and menu bookkeeping).
+    ///
+    /// Core lifecycle events (`placed`, `cancelled`, `advanced`,
+    /// `setstatus`, disputes) are always emitted; high-volume deployments
+    /// can switch the rest off to save resources.
+    pub fn set_verbose_events(env: Env, caller: Address, enabled: bool) {
+        caller.require_auth();
+        Self::assert_admin_or_panic(&env, &caller);
+        env.storage().instance().set(&DataKey::VerboseEvents, &enabled);
+        Self::extend_instance(&env);
+    }
+
+    // -----------------------------------------------------------------------
+    // Menu pricing (admin)
+    // -----------------------------------------------------------------------
+
+    /// Record the current menu price of an item, in stroops.
+    pub fn set_menu_price(
+        env: Env,
+        caller: Address,
+        restaurant_id: u64,
+        menu_item_id: u64,
+        price: i128,
+    ) {
+        caller.require_auth();
+        Self::assert_admin_or_panic(&env, &caller);
+        if price <= 0 {
+            panic!("unit price must be positive");
+        }
+
+        let key = DataKey::MenuPrice(restaurant_id, menu_item_id);
+        let ttl = Self::persistent_ttl(&env);
+        env.storage().persistent().set(&key, &price);
+        env.storage().persistent().extend_ttl(&key, ttl, ttl);
+
+        if Self::verbose_events(&env) {
+            let data = (restaurant_id, menu_item_id, price);
+            Self::emit(&env, symbol_short!("menuprice"), data);
+        }
+    }
+
+    /// Toggle strict pricing.  In strict mode every ordered item must carry
+    /// the current menu price, otherwise `place_order` panics with
+    /// "stale price" and the client must refetch the menu.  Lenient mode
+    /// (the default) suits deployments that keep no on-chain menu.
+    pub fn set_strict_pricing(env: Env, caller: Address, strict: bool) {
+        caller.require_auth();
+        Self::assert_admin_or_panic(&env, &caller);
+        env.storage().instance().set(&DataKey::StrictPricing, &strict);
+        Self::extend_instance(&env);
+    }
+
+    // -----------------------------------------------------------------------
+    // Reward configuration (admin)
+    // -----------------------------------------------------------------------
+
+    /// Set the BITE loyalty token that delivery rewards are minted from.
+    pub fn set_loyalty_token(env: Env, caller: Address, token: Address) {
+        caller.require_auth();
+        Self::assert_admin_or_panic(&env, &caller);
+        env.storage().instance().set(&DataKey::LoyaltyToken, &token);
+        Self::extend_instance(&env);
+    }
+
+    /// Set the reward rate in basis points of the order total (max 10 000).
+    pub fn set_reward_rate(env: Env, caller: Address, rate_bps: u32) {
+        caller.require_auth();
+        Self::assert_admin_or_panic(&env, &caller);
+        if rate_bps > 10_000 {
+            panic!("reward rate cannot exceed 10000 bps");
+        }
+        env.storage().instance().set(&DataKey::RewardRateBps, &rate_bps);
+        Self::extend_instance(&env);
+    }
+
+    /// Set the highest reward rate a restaurant may configure for itself
+    /// (admin only).  Existing overrides above it stay stored but pay out
+    /// at most the new maximum.
+    pub fn set_max_restaurant_reward_rate(env: Env, caller: Address, rate_bps: u32) {
+        caller.require_auth();
+        Self::assert_admin_or_panic(&env, &caller);
+        if rate_bps > 10_000 {
+            panic!("reward rate cannot exceed 10000 bps");
+        }
+        env.storage()
+            .instance()
+            .set(&DataKey::MaxRestaurantRewardBps, &rate_bps);
+        Self::extend_instance(&env);
+    }
+
+    /// Override the reward rate for one restaurant's orders.  Callable by
+    /// the admin or the restaurant's owner, up to the admin-set maximum.
+    /// `0` removes the override so the global rate applies again.
+    pub fn set_restaurant_reward_rate(
+        env: Env,
+        caller: Address,
+        restaurant_id: u64,
+        rate_bps: u32,
+    ) {
+        caller.require_auth();
+        Self::assert_admin_or_owner(&env, &caller, restaurant_id);
+        let key = DataKey::RestaurantRewardRate(restaurant_id);
+        if rate_bps == 0 {
+            env.storage().persistent().remove(&key);
+            return;
+        }
+        let max: u32 = env
+            .storage()
+            .instance()
+            .get(&DataKey::MaxRestaurantRewardBps)
+            .unwrap_or(0);
+        if rate_bps > max {
+            panic!("reward rate exceeds restaurant maximum");
+        }
+        let ttl = Self::persistent_ttl(&env);
+        env.storage().persistent().set(&key, &rate_bps);
+        env.storage().persistent().extend_ttl(&key, ttl, ttl);
+    }
+
+    /// Set the minimum number of seconds between two rewards for the same
+    /// customer.  Deliveries inside the window still complete; only the
+    /// reward is skipped.  Pass `0` to disable.
+    pub fn set_reward_cooldown(env: Env, caller: Address, cooldown_secs: u64) {
+        caller.require_auth();
+        Self::assert_admin_or_panic(&env, &caller);
+        env.storage()
+            .instance()
+            .set(&DataKey::RewardCooldown, &cooldown_secs);
+        Self::extend_instance(&env);
+    }
+
+    /// Set the bonus minted on top of the normal reward for a customer's
+    /// first delivered order.  Pass `0` to disable.
+    pub fn set_first_order_bonus(env: Env, caller: Address, bonus: i128) {
+        caller.require_auth();
+        Self::assert_admin_or_panic(&env, &caller);
+        if bonus < 0 {
+            panic!("bonus cannot be negative");
+        }
+        env.storage().instance().set(&DataKey::FirstOrderBonus, &bonus);
+        Self::extend_instance(&env);
+    }
+
+    /// Switch between minting rewards on delivery (default) and accruing
+    /// them for the customer to claim with `claim_rewards`.
+    pub fn set_claim_mode(env: Env, caller: Address, enabled: bool) {
+        caller.require_auth();
+        Self::assert_admin_or_panic(&env, &caller);
+        env.storage().instance().set(&DataKey::ClaimMode, &enabled);
+        Self::extend_instance(&env);
+    }
+
+    /// Choose how fractional rewards are rounded (defaults to `Floor`).
+    pub fn set_round_mode(env: Env, caller: Address, mode: RoundMode) {
+        caller.require_auth();
+        Self::assert_admin_or_panic(&env, &caller);
+        env.storage().instance().set(&DataKey::RoundMode, &mode);
+        Self::extend_instance(&env);
+    }
+
+    /// Schedule a promotional multiplier on the rate-based reward for
+    /// deliveries in `[start, end)`.  Replaces any previous promo.
+    pub fn set_promo(env: Env, caller: Address, multiplier_bps: u32, start: u64, end: u64) {
+        caller.require_auth();
+        Self::assert_admin_or_panic(&env, &caller);
+        if end <= start {
+            panic!("promo must end after it starts");
+        }
+        let promo = Promo {
+            multiplier_bps,
+            start,
+            end,
+        };
+        env.storage().instance().set(&DataKey::Promo, &promo);
+        Self::extend_instance(&env);
+    }
+
+    /// Set structural rules for order notes (admin only): a maximum byte
+    /// length (`0` = unlimited) and whether ASCII control characters other
+    /// than tab, LF and CR are rejected.  Content is not inspected beyond
+    /// that.
+    pub fn set_notes_policy(env: Env, caller: Address, max_len: u32, reject_control_chars: bool) {
+        caller.require_auth();
+        Self::assert_admin_or_panic(&env, &caller);
+        let policy = NotesPolicy {
+            max_len,
+            reject_control_chars,
+        };
+        env.storage().instance().set(&DataKey::NotesPolicy, &policy);
+        Self::extend_instance(&env);
+    }
+
+    /// Lock rewards minted while a promo is running for `lock_secs`
+    /// seconds (admin only), so promo BITE cannot be dumped straight away.
+    /// `0` mints promo rewards liquid like any other reward.
+    pub fn set_promo_lock(env: Env, caller: Address, lock_secs: u64) {
+        caller.require_auth();
+        Self::assert_admin_or_panic(&env, &caller);
+        env.storage()
+            .instance()
+            .set(&DataKey::PromoLockSecs, &lock_secs);
+        Self::extend_instance(&env);
+    }
+
+    // -----------------------------------------------------------------------
+    // View functions
+    // -----------------------------------------------------------------------
+
+    /// Fetch a single order by ID.
+    pub fn get_order(env: Env, order_id: u64) -> Order {
+        Self::load_order(&env, order_id)
+    }
+
+    /// Whether an order with this ID exists.
+    pub fn order_exists(env: Env, order_id: u64) -> bool {
+        env.storage().persistent().has(&DataKey::Order(order_id))
+    }
+
+    /// Return a list of order IDs for a restaurant.
+    pub fn get_restaurant_orders(env: Env, restaurant_id: u64) -> Vec<u64> {
+        env.storage()
             .persistent()
-            .set(&DataKey::Order(order.id), order);
+            .get(&DataKey::RestaurantOrders(restaurant_id))
+            .unwrap_or_else(|| vec![&env])
+    }
+
+    /// Return order IDs for several restaurants in one call.
+    ///
+    /// Each restaurant contributes at most `limit_per` IDs (oldest first) and
+    /// the combined result is capped at `MAX_RESULTS`.
+    pub fn get_orders_for_restaurants(
+        env: Env,
+        restaurant_ids: Vec<u64>,
+        limit_per: u32,
+    ) -> Vec<u64> {
+        let mut result: Vec<u64> = vec![&env];
+        for restaurant_id in restaurant_ids.iter() {
+            let orders = Self::get_restaurant_orders(env.clone(), restaurant_id);
+            for order_id in orders.iter().take(limit_per as usize) {
+                if result.len() >= MAX_RESULTS {
+                    return result;
+                }
+                result.push_back(order_id);
+            }
+        }
+        result
+    }
+
+    /// A restaurant's kitchen queue: orders that are `Pending` (to confirm)
+    /// or `Confirmed`/`Preparing` (to progress), oldest first, up to
+    /// `limit` (capped at `MAX_RESULTS`).  Only the restaurant's latest
+    /// `MAX_SCAN` orders are inspected.
+    pub fn get_actionable_orders(env: Env, restaurant_id: u64, limit: u32) -> Vec<u64> {
+        let limit = limit.min(MAX_RESULTS);
+        let ids = Self::get_restaurant_orders(env.clone(), restaurant_id);
+        let start = ids.len().saturating_sub(MAX_SCAN);
+        let mut result: Vec<u64> = vec![&env];
+        for order_id in ids.slice(start..).iter() {
+            if result.len() >= limit {
+                break;
+            }
+            let order: Option<Order> = env.storage().persistent().get(&DataKey::Order(order_id));
+            if order.is_some_and(|o| {
+                matches!(
+                    o.status,
+                    OrderStatus::Pending | OrderStatus::Confirmed | OrderStatus::Preparing
+                )
+            }) {
+                result.push_back(order_id);
+            }
+        }
+        result
+    }
+
+    /// Page through every order ID in `[start_id, start_id + limit)`,
+    /// skipping IDs whose order entry has expired.  `limit` is capped at
+    /// `MAX_RESULTS`.
+    pub fn get_orders_global(env: Env, start_id: u64, limit: u32) -> Vec<u64> {
+        let count = Self::get_count(env.clone());
+        let end = start_id
+            .saturating_add(limit.min(MAX_RESULTS) as u64)
+            .min(count + 1);
+        let mut result: Vec<u64> = vec![&env];
+        for order_id in start_id.max(1)..end {
+            if Self::order_exists(env.clone(), order_id) {
+                result.push_back(order_id);
+            }
+        }
+        result
+    }
+
+    /// Order, active-order and delivered-order counts plus revenue for a
+    /// restaurant.  Orders placed before these counters existed are not
+    /// included.
+    pub fn get_restaurant_stats(env: Env, restaurant_id: u64) -> RestaurantStats {
         env.storage()
             .persistent()
-            .extend_ttl(&DataKey::Order(order.id), ttl, ttl);
+            .get(&DataKey::RestaurantStats(restaurant_id))
+            .unwrap_or(RestaurantStats {
+                order_count: 0,
+                active_orders: 0,
+                delivered_orders: 0,
+                total_revenue: 0,
+            })
+    }
+
+    /// Average value of a restaurant's delivered orders, in stroops
+    /// (`0` if none have been delivered).  Cancelled orders are ignored.
+    pub fn get_average_order_value(env: Env, restaurant_id: u64) -> i128 {
+        let stats = Self::get_restaurant_stats(env, restaurant_id);
+        if stats.delivered_orders == 0 {
+            return 0;
+        }
+        stats.total_revenue / stats.delivered_orders as i128
     }
 
-    fn assert_admin_or_panic(env: &Env, caller: &Address) {
-        let admin: Address = env.storage().instance().get(&DataKey::Admin).unwrap();
-        if caller != &admin {
-            panic!("unauthorized: admin only");
+    /// Distribution of a restaurant's delivered (or closed) order totals.
+    ///
+    /// Bucket `i` counts totals in `[i * bucket_size, (i + 1) * bucket_size)`;
+    /// the last bucket also takes everything above it.  At most
+    /// `MAX_HISTOGRAM_BUCKETS` buckets, over the restaurant's latest
+    /// `MAX_SCAN` orders.
+    pub fn get_order_value_histogram(
+        env: Env,
+        restaurant_id: u64,
+        bucket_size: i128,
+        num_buckets: u32,
+    ) -> Vec<u64> {
+        if bucket_size <= 0 {
+            panic!("bucket size must be positive");
+        }
+        if num_buckets == 0 || num_buckets > MAX_HISTOGRAM_BUCKETS {
+            panic!("invalid bucket count");
+        }
+
+        let mut buckets: Vec<u64> = vec![&env];
+        for _ in 0..num_buckets {
+            buckets.push_back(0);
+        }
+        let ids = Self::get_restaurant_orders(env.clone(), restaurant_id);
+        let start = ids.len().saturating_sub(MAX_SCAN);
+        for order_id in ids.slice(start..).iter() {
+            let order: Order = match env.storage().persistent().get(&DataKey::Order(order_id)) {
+                Some(order) => order,
+                None => continue,
+            };
+            if !matches!(order.status, OrderStatus::Delivered | OrderStatus::Closed) {
+                continue;
+            }
+            let index = (order.total_amount / bucket_size).min(num_buckets as i128 - 1) as u32;
+            buckets.set(index, buckets.get(index).unwrap() + 1);
+        }
+        buckets
+    }
+
+    /// Order, delivery, cancellation and volume totals across all
+    /// restaurants, read from maintained counters.
+    pub fn get_global_summary(env: Env) -> GlobalSummary {
+        env.storage()
+            .instance()
+            .get(&DataKey::GlobalSummary)
+            .unwrap_or(GlobalSummary {
+                total_orders: 0,
+                total_delivered: 0,
+                total_cancelled: 0,
+                total_volume: 0,
+            })
+    }
+
+    /// Return a list of order IDs for a customer.
+    pub fn get_customer_orders(env: Env, customer: Address) -> Vec<u64> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::CustomerOrders(customer))
+            .unwrap_or_else(|| vec![&env])
+    }
+
+    /// The customer's orders at one restaurant, oldest first, up to `limit`
+    /// (capped at `MAX_RESULTS`).  Only the customer's latest `MAX_SCAN`
+    /// orders are inspected.
+    pub fn get_customer_restaurant_orders(
+        env: Env,
+        customer: Address,
+        restaurant_id: u64,
+        limit: u32,
+    ) -> Vec<u64> {
+        let limit = limit.min(MAX_RESULTS);
+        let ids = Self::get_customer_orders(env.clone(), customer);
+        let start = ids.len().saturating_sub(MAX_SCAN);
+        let mut result: Vec<u64> = vec![&env];
+        for order_id in ids.slice(start..).iter() {
+            if result.len() >= limit {
+                break;
+            }
+            let order: Option<Order> = env.storage().persistent().get(&DataKey::Order(order_id));
+            if order.is_some_and(|o| o.restaurant_id == restaurant_id) {
+                result.push_back(order_id);
+            }
+        }
+        result
+    }
+
+    /// Profile summary for a customer: orders placed, delivered spend,
+    /// favourite restaurant and BITE earned.  Spend, favourite and BITE
+    /// only reflect activity since these counters were introduced.
+    pub fn get_customer_stats(env: Env, customer: Address) -> CustomerStats {
+        let mut stats: CustomerStats = env
+            .storage()
+            .persistent()
+            .get(&DataKey::CustomerStats(customer.clone()))
+            .unwrap_or(CustomerStats {
+                order_count: 0,
+                total_spent: 0,
+                favorite_restaurant: 0,
+                favorite_orders: 0,
+                bite_earned: 0,
+            });
+        stats.order_count = Self::get_customer_orders(env, customer).len();
+        stats
+    }
+
+    /// The customer's most recently placed order, if any.
+    pub fn get_last_order_for_customer(env: Env, customer: Address) -> Option<Order> {
+        let orders = Self::get_customer_orders(env.clone(), customer);
+        orders
+            .last()
+            .map(|order_id| Self::load_order(&env, order_id))
+    }
+
+    /// Statuses in which customers may cancel their own orders.
+    pub fn get_customer_cancellable(env: Env) -> Vec<OrderStatus> {
+        env.storage()
+            .instance()
+            .get(&DataKey::CustomerCancellable)
+            .unwrap_or_else(|| vec![&env, OrderStatus::Pending])
+    }
+
+    /// Clawed-back BITE the customer still owes against future rewards.
+    pub fn get_reward_debt(env: Env, customer: Address) -> i128 {
+        env.storage()
+            .persistent()
+            .get(&DataKey::RewardDebt(customer))
+            .unwrap_or(0)
+    }
+
+    /// Rewards accrued in claim mode and not yet claimed.
+    pub fn get_pending_reward(env: Env, customer: Address) -> i128 {
+        env.storage()
+            .persistent()
+            .get(&DataKey::PendingReward(customer))
+            .unwrap_or(0)
+    }
+
+    /// Unclaimed BITE the customer has accrued in claim mode, i.e. exactly
+    /// what `claim_rewards` would mint for them now.
+    pub fn pending_rewards(env: Env, customer: Address) -> i128 {
+        Self::get_pending_reward(env, customer)
+    }
+
+    /// Current menu price of an item, or `0` if none is recorded.
+    pub fn get_menu_price(env: Env, restaurant_id: u64, menu_item_id: u64) -> i128 {
+        env.storage()
+            .persistent()
+            .get(&DataKey::MenuPrice(restaurant_id, menu_item_id))
+            .unwrap_or(0)
+    }
+
+    /// Return the customer's BITE balance from the configured loyalty token,
+    /// so front ends only need this contract's address.  Returns `0` while
+    /// no loyalty token is configured.
+    pub fn get_customer_rewards(env: Env, customer: Address) -> i128 {
+        let token: Option<Address> = env.storage().instance().get(&DataKey::LoyaltyToken);
+        match token {
+            Some(token) => LoyaltyClient::new(&env, &token).balance(&customer),
+            None => 0,
+        }
+    }
+
+    /// BITE the customer would receive if this order were delivered now,
+    /// using the same rate, rounding, first-order bonus, cooldown and
+    /// reward-debt rules as the mint on delivery.  Returns `0` for delivered or cancelled
+    /// orders and while no loyalty token is configured.
+    pub fn preview_reward(env: Env, order_id: u64) -> i128 {
+        let order = Self::get_order(env.clone(), order_id);
+        if !Self::is_open(&order.status) {
+            return 0;
+        }
+        if !env.storage().instance().has(&DataKey::LoyaltyToken) {
+            return 0;
+        }
+        let delivered: u32 = env
+            .storage()
+            .persistent()
+            .get(&DataKey::CustomerDelivered(order.customer.clone()))
+            .unwrap_or(0);
+        let amount = Self::reward_amount(&env, &order, delivered == 0);
+        if amount <= 0 || Self::in_reward_cooldown(&env, &order.customer) {
+            return 0;
+        }
+        let debt = Self::get_reward_debt(env, order.customer);
+        amount - debt.min(amount)
+    }
+
+    /// Order IDs placed together under `cart_id` (empty if unknown).
+    pub fn get_cart_orders(env: Env, cart_id: u64) -> Vec<u64> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Cart(cart_id))
+            .unwrap_or_else(|| vec![&env])
+    }
+
+    /// Status changes of an order as `(status, timestamp)` pairs, starting
+    /// with its placement.  Only the latest `MAX_TIMELINE` entries are kept.
+    pub fn get_order_timeline(env: Env, order_id: u64) -> Vec<(OrderStatus, u64)> {
+        env.storage()
+            .persistent()
+            .get(&DataKey::OrderTimeline(order_id))
+            .unwrap_or_else(|| vec![&env])
+    }
+
+    /// Whether `customer` is blocked from ordering at the restaurant.
+    pub fn is_blocked(env: Env, restaurant_id: u64, customer: Address) -> bool {
+        env.storage()
+            .persistent()
+            .has(&DataKey::Blocked(restaurant_id, customer))
+    }
+
+    /// Whether `set_status` may move an order from `from` to `to`.
+    pub fn is_transition_allowed(env: Env, from: OrderStatus, to: OrderStatus) -> bool {
+        env.storage()
+            .persistent()
+            .has(&DataKey::AllowedTransition(from, to))
+    }
+
+    /// Auto-confirm window for a restaurant in seconds (0 = disabled).
+    pub fn get_auto_confirm(env: Env, restaurant_id: u64) -> u64 {
+        env.storage()
+            .persistent()
+            .get(&DataKey::AutoConfirmSecs(restaurant_id))
+            .unwrap_or(0)
+    }
+
+    /// Cart the order was checked out under, or `None` for single orders.
+    pub fn get_order_cart(env: Env, order_id: u64) -> Option<u64> {
+        env.storage().persistent().get(&DataKey::OrderCart(order_id))
+    }
+
+    /// Why the order was cancelled, or `None` if it is not cancelled.
+    pub fn get_cancel_reason(env: Env, order_id: u64) -> Option<CancelReason> {
+        env.storage().persistent().get(&DataKey::CancelReason(order_id))
+    }
+
+    /// Dry-run of `place_order`: apply the same checks to `items` and
+    /// `notes` and return the order total without storing anything or
+    /// emitting events.  Panics with the same message `place_order` would.
+    pub fn validate_order(
+        env: Env,
+        restaurant_id: u64,
+        items: Vec<OrderItem>,
+        notes: String,
+    ) -> i128 {
+        Self::check_notes(&env, &notes);
+        Self::validate_new_order(&env, restaurant_id, &items)
+    }
+
+    /// Sequence number carried by the most recent event (0 before any).
+    /// Indexers can persist it and detect gaps after reconnecting.
+    pub fn current_event_seq(env: Env) -> u64 {
+        env.storage()
+            .instance()
+            .get(&DataKey::EventSeq)
+            .unwrap_or(0)
+    }
+
+    /// Total orders ever placed.
+    pub fn get_count(env: Env) -> u64 {
+        env.storage().instance().get(&DataKey::Count).unwrap_or(0)
+    }
+
+    // -----------------------------------------------------------------------
+    // Private helpers
+    // -----------------------------------------------------------------------
+
+    fn load_order(env: &Env, order_id: u64) -> Order {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Order(order_id))
+            .unwrap_or_else(|| panic!("order not found"))
+    }
+
+    fn persistent_ttl(env: &Env) -> u32 {
+        env.storage()
+            .instance()
+            .get(&DataKey::PersistentTtl)
+            .unwrap_or(DEFAULT_PERSISTENT_TTL)
+    }
+
+    fn extend_instance(env: &Env) {
+        let ttl: u32 = env
+            .storage()
+            .instance()
+            .get(&DataKey::InstanceTtl)
+            .unwrap_or(DEFAULT_INSTANCE_TTL);
+        env.storage().instance().extend_ttl(ttl, ttl);
+    }
+
+    fn save_order(env: &Env, order: &Order) {
+        let ttl = Self::persistent_ttl(env);
+        env.storage()
+            .persistent()
+            .set(&DataKey::Order(order.id), order);
+        env.storage()
+            .persistent()
+            .extend_ttl(&DataKey::Order(order.id), ttl, ttl);
+    }
+
+    /// Validate and store a new order, returning its ID.  Callers are
+    /// responsible for the customer's authorisation.  A `notes_ref` marks
+    /// the notes as encrypted off-chain.
+    fn create_order(
+        env: &Env,
+        customer: Address,
+        restaurant_id: u64,
+        items: Vec<OrderItem>,
+        notes: String,
+        notes_ref: Option<String>,
+    ) -> u64 {
+        if Self::is_blocked(env.clone(), restaurant_id, customer.clone()) {
+            panic!("customer blocked");
+        }
+        Self::check_notes(env, &notes);
+        let total = Self::validate_new_order(env, restaurant_id, &items);
+
+        let count: u64 = env
+            .storage()
+            .instance()
+            .get(&DataKey::Count)
+            .unwrap_or(0);
+        let id: u64 = count + 1;
+        let now = env.ledger().timestamp();
+
+        let order = Order {
+            id,
+            restaurant_id,
+            customer: customer.clone(),
+            items: items.clone(),
+            total_amount: total,
+            status: OrderStatus::Pending,
+            created_at: now,
+            updated_at: now,
+            confirmed_at: 0,
+            delivered_at: 0,
+            notes,
+            disputed: false,
+            notes_encrypted: notes_ref.is_some(),
+            notes_ref: notes_ref.unwrap_or_else(|| String::from_str(env, "")),
+            reward_minted: false,
+        };
+
+        let ttl = Self::persistent_ttl(env);
+        env.storage()
+            .persistent()
+            .set(&DataKey::Order(id), &order);
+        env.storage()
+            .persistent()
+            .extend_ttl(&DataKey::Order(id), ttl, ttl);
+
+        // Append to restaurant index.
+        Self::append_to_list(
+            env,
+            DataKey::RestaurantOrders(restaurant_id),
+            id,
+            ttl,
+        );
+        // Append to customer index.
+        Self::append_to_list(
+            env,
+            DataKey::CustomerOrders(customer.clone()),
+            id,
+            ttl,
+        );
+
+        let mut stats = Self::get_restaurant_stats(env.clone(), restaurant_id);
+        stats.order_count += 1;
+        stats.active_orders += 1;
+        Self::save_restaurant_stats(env, restaurant_id, &stats);
+        Self::track_customer_order(env, &customer, restaurant_id, ttl);
+        let mut summary = Self::get_global_summary(env.clone());
+        summary.total_orders += 1;
+        env.storage().instance().set(&DataKey::GlobalSummary, &summary);
+
+        env.storage().instance().set(&DataKey::Count, &id);
+        Self::extend_instance(env);
+        Self::record_timeline(env, id, OrderStatus::Pending);
+
+        Self::emit(env, symbol_short!("placed"), (id, restaurant_id, customer, total));
+
+        id
+    }
+
+    /// Check a prospective order and return its total.  When a registry is
+    /// configured the restaurant must currently be accepting orders.
+    fn validate_new_order(env: &Env, restaurant_id: u64, items: &Vec<OrderItem>) -> i128 {
+        let registry: Option<Address> = env.storage().instance().get(&DataKey::RegistryContract);
+        if let Some(registry) = registry {
+            if !RegistryClient::new(env, &registry).is_accepting_orders(&restaurant_id) {
+                panic!("restaurant not accepting orders");
+            }
+        }
+        if items.is_empty() {
+            panic!("order must contain at least one item");
+        }
+
+        // Compute total from items.
+        let mut total: i128 = 0;
+        for item in items.iter() {
+            if item.quantity == 0 {
+                panic!("quantity must be greater than zero");
+            }
+            if item.unit_price <= 0 {
+                panic!("unit price must be positive");
+            }
+            total += item.unit_price * item.quantity as i128;
+        }
+        Self::check_menu_prices(env, restaurant_id, items);
+        total
+    }
+
+    /// Whether the order's customer is still allowed to cancel it.
+    fn customer_may_cancel(env: &Env, order: &Order) -> bool {
+        let grace: u64 = env
+            .storage()
+            .instance()
+            .get(&DataKey::CancelGraceSecs)
+            .unwrap_or(0);
+        let deadline = order.confirmed_at.saturating_add(grace);
+        let in_grace = grace > 0 && env.ledger().timestamp() <= deadline;
+        let cancellable = Self::get_customer_cancellable(env.clone());
+        if cancellable.contains(&order.status) {
+            // Once confirmed, a configured grace window bounds every status.
+            return order.confirmed_at == 0 || grace == 0 || in_grace;
+        }
+        order.status == OrderStatus::Confirmed && in_grace
+    }
+
+    fn assert_admin_or_panic(env: &Env, caller: &Address) {
+        let admin: Address = env.storage().instance().get(&DataKey::Admin).unwrap();
+        if caller != &admin {
+            panic!("unauthorized: admin only");
+        }
+    }
+
+    /// Allow the admin, or the owner of `restaurant_id` when a registry is
+    /// configured.
+    fn assert_admin_or_owner(env: &Env, caller: &Address, restaurant_id: u64) {
+        let admin: Address = env.storage().instance().get(&DataKey::Admin).unwrap();
+        if caller == &admin {
+            return;
+        }
+        let registry: Option<Address> = env.storage().instance().get(&DataKey::RegistryContract);
+        let owns = registry.is_some_and(|registry| {
+            let owned = RegistryClient::new(env, &registry).try_get_owner_restaurant(caller);
+            matches!(owned, Ok(Ok(id)) if id == restaurant_id)
+        });
+        if !owns {
+            panic!("unauthorized");
+        }
+    }
+
+    /// Enforce the admin's `NotesPolicy`, if any, on plaintext notes.
+    fn check_notes(env: &Env, notes: &String) {
+        let policy: NotesPolicy = match env.storage().instance().get(&DataKey::NotesPolicy) {
+            Some(policy) => policy,
+            None => return,
+        };
+        let len = notes.len();
+        if policy.max_len > 0 && len > policy.max_len {
+            panic!("notes too long");
+        }
+        if policy.reject_control_chars {
+            // Notes that cannot be scanned are rejected rather than trusted.
+            if len > MAX_NOTES_SCAN {
+                panic!("notes too long");
+            }
+            let mut buf = [0u8; MAX_NOTES_SCAN as usize];
+            let bytes = &mut buf[..len as usize];
+            notes.copy_into_slice(bytes);
+            let allowed = |b: u8| b == b'\t' || b == b'\n' || b == b'\r';
+            if bytes.iter().any(|&b| (b < 0x20 && !allowed(b)) || b == 0x7f) {
+                panic!("notes contain disallowed characters");
+            }
+        }
+    }
+
+    /// In strict pricing mode, ensure every item matches the recorded menu.
+    fn check_menu_prices(env: &Env, restaurant_id: u64, items: &Vec<OrderItem>) {
+        let strict: bool = env
+            .storage()
+            .instance()
+            .get(&DataKey::StrictPricing)
+            .unwrap_or(false);
+        if !strict {
+            return;
+        }
+        for item in items.iter() {
+            let price: Option<i128> = env
+                .storage()
+                .persistent()
+                .get(&DataKey::MenuPrice(restaurant_id, item.menu_item_id));
+            match price {
+                None => panic!("item not on menu"),
+                Some(price) if price != item.unit_price => panic!("stale price"),
+                Some(_) => {}
+            }
+        }
+    }
+
+    /// Reward earned by `order` under the current rate and rounding mode.
+    fn compute_reward(env: &Env, order: &Order) -> i128 {
+        let rate_override: Option<u32> = env
+            .storage()
+            .persistent()
+            .get(&DataKey::RestaurantRewardRate(order.restaurant_id));
+        let rate_bps: u32 = match rate_override {
+            Some(rate) => {
+                let max: u32 = env
+                    .storage()
+                    .instance()
+                    .get(&DataKey::MaxRestaurantRewardBps)
+                    .unwrap_or(0);
+                rate.min(max)
+            }
+            None => env
+                .storage()
+                .instance()
+                .get(&DataKey::RewardRateBps)
+                .unwrap_or(0),
+        };
+        let mode: RoundMode = env
+            .storage()
+            .instance()
+            .get(&DataKey::RoundMode)
+            .unwrap_or(RoundMode::Floor);
+
+        let scaled = order.total_amount * rate_bps as i128;
+        match mode {
+            RoundMode::Floor => scaled / 10_000,
+            RoundMode::Nearest => (scaled + 5_000) / 10_000,
+            RoundMode::Ceil => (scaled + 9_999) / 10_000,
+        }
+    }
+
+    /// Rate-based reward, scaled by any active promo, plus the first-order
+    /// bonus when it applies.
+    fn reward_amount(env: &Env, order: &Order, first_delivery: bool) -> i128 {
+        let mut amount = Self::compute_reward(env, order);
+        if let Some(promo) = Self::active_promo(env) {
+            amount = amount * promo.multiplier_bps as i128 / 10_000;
+        }
+        if first_delivery {
+            let bonus: i128 = env
+                .storage()
+                .instance()
+                .get(&DataKey::FirstOrderBonus)
+                .unwrap_or(0);
+            amount += bonus;
+        }
+        amount
+    }
+
+    /// The configured promo, if the current ledger time falls inside it.
+    fn active_promo(env: &Env) -> Option<Promo> {
+        let promo: Promo = env.storage().instance().get(&DataKey::Promo)?;
+        let now = env.ledger().timestamp();
+        if now >= promo.start && now < promo.end {
+            Some(promo)
+        } else {
+            None
+        }
+    }
+
+    /// Whether the customer was rewarded within the cooldown window.
+    fn in_reward_cooldown(env: &Env, customer: &Address) -> bool {
+        let cooldown: u64 = env
+            .storage()
+            .instance()
+            .get(&DataKey::RewardCooldown)
+            .unwrap_or(0);
+        if cooldown == 0 {
+            return false;
+        }
+        let last: Option<u64> = env
+            .storage()
+            .persistent()
+            .get(&DataKey::LastReward(customer.clone()));
+        match last {
+            Some(last) => env.ledger().timestamp() < last.saturating_add(cooldown),
+            None => false,
+        }
+    }
+
+    /// Whether an order in `status` is still in progress.
+    fn is_open(status: &OrderStatus) -> bool {
+        !matches!(
+            status,
+            OrderStatus::Delivered | OrderStatus::Cancelled | OrderStatus::Closed
+        )
+    }
+
+    /// Update the restaurant and platform counters after `order` moved away
+    /// from `previous`.
+    fn track_status_change(env: &Env, order: &Order, previous: OrderStatus) {
+        let delivered = |status: &OrderStatus| {
+            *status == OrderStatus::Delivered || *status == OrderStatus::Closed
+        };
+        let mut stats = Self::get_restaurant_stats(env.clone(), order.restaurant_id);
+        match (Self::is_open(&previous), Self::is_open(&order.status)) {
+            (true, false) => stats.active_orders = stats.active_orders.saturating_sub(1),
+            (false, true) => stats.active_orders += 1,
+            _ => {}
+        }
+        let mut summary = Self::get_global_summary(env.clone());
+        match (delivered(&previous), delivered(&order.status)) {
+            (false, true) => {
+                stats.delivered_orders += 1;
+                stats.total_revenue += order.total_amount;
+                summary.total_delivered += 1;
+                summary.total_volume += order.total_amount;
+                Self::update_customer_stats(env, &order.customer, |c| {
+                    c.total_spent += order.total_amount
+                });
+            }
+            (true, false) => {
+                stats.delivered_orders = stats.delivered_orders.saturating_sub(1);
+                stats.total_revenue -= order.total_amount;
+                summary.total_delivered = summary.total_delivered.saturating_sub(1);
+                summary.total_volume -= order.total_amount;
+                Self::update_customer_stats(env, &order.customer, |c| {
+                    c.total_spent -= order.total_amount
+                });
+            }
+            _ => {}
+        }
+        let cancelled = |status: &OrderStatus| *status == OrderStatus::Cancelled;
+        match (cancelled(&previous), cancelled(&order.status)) {
+            (false, true) => summary.total_cancelled += 1,
+            (true, false) => summary.total_cancelled = summary.total_cancelled.saturating_sub(1),
+            _ => {}
+        }
+        Self::save_restaurant_stats(env, order.restaurant_id, &stats);
+        env.storage().instance().set(&DataKey::GlobalSummary, &summary);
+        Self::record_timeline(env, order.id, order.status.clone());
+    }
+
+    /// Append a status change to the order's timeline, dropping the oldest
+    /// entry once `MAX_TIMELINE` is reached.
+    fn record_timeline(env: &Env, order_id: u64, status: OrderStatus) {
+        let key = DataKey::OrderTimeline(order_id);
+        let mut timeline = Self::get_order_timeline(env.clone(), order_id);
+        if timeline.len() >= MAX_TIMELINE {
+            timeline.pop_front();
+        }
+        timeline.push_back((status, env.ledger().timestamp()));
+        let ttl = Self::persistent_ttl(env);
+        env.storage().persistent().set(&key, &timeline);
+        env.storage().persistent().extend_ttl(&key, ttl, ttl);
+    }
+
+    /// Store (or with `None`, clear) the cancellation reason for an order.
+    fn set_cancel_reason(env: &Env, order_id: u64, reason: Option<CancelReason>) {
+        let key = DataKey::CancelReason(order_id);
+        match reason {
+            Some(reason) => {
+                let ttl = Self::persistent_ttl(env);
+                env.storage().persistent().set(&key, &reason);
+                env.storage().persistent().extend_ttl(&key, ttl, ttl);
+            }
+            None => env.storage().persistent().remove(&key),
+        }
+    }
+
+    /// Count a new order towards the customer's favourite restaurant.
+    fn track_customer_order(env: &Env, customer: &Address, restaurant_id: u64, ttl: u32) {
+        let key = DataKey::CustomerRestaurantCount(customer.clone(), restaurant_id);
+        let count: u32 = env.storage().persistent().get(&key).unwrap_or(0) + 1;
+        env.storage().persistent().set(&key, &count);
+        env.storage().persistent().extend_ttl(&key, ttl, ttl);
+        Self::update_customer_stats(env, customer, |c| {
+            if count > c.favorite_orders {
+                c.favorite_restaurant = restaurant_id;
+                c.favorite_orders = count;
+            }
+        });
+    }
+
+    /// Take an order back from the customer's count for `restaurant_id`,
+    /// e.g. when it moves to another branch.  If that restaurant was the
+    /// favourite it stays so, with the lowered count.
+    fn untrack_customer_order(env: &Env, customer: &Address, restaurant_id: u64) {
+        let key = DataKey::CustomerRestaurantCount(customer.clone(), restaurant_id);
+        let count: u32 = env.storage().persistent().get(&key).unwrap_or(0);
+        let count = count.saturating_sub(1);
+        if count > 0 {
+            let ttl = Self::persistent_ttl(env);
+            env.storage().persistent().set(&key, &count);
+            env.storage().persistent().extend_ttl(&key, ttl, ttl);
+        } else {
+            env.storage().persistent().remove(&key);
+        }
+        Self::update_customer_stats(env, customer, |c| {
+            if c.favorite_restaurant == restaurant_id {
+                c.favorite_orders = count;
+            }
+        });
+    }
+
+    /// Apply `f` to the customer's stored aggregates and save them.
+    fn update_customer_stats(env: &Env, customer: &Address, f: impl FnOnce(&mut CustomerStats)) {
+        let mut stats = Self::get_customer_stats(env.clone(), customer.clone());
+        f(&mut stats);
+        let key = DataKey::CustomerStats(customer.clone());
+        let ttl = Self::persistent_ttl(env);
+        env.storage().persistent().set(&key, &stats);
+        env.storage().persistent().extend_ttl(&key, ttl, ttl);
+    }
+
+    fn save_restaurant_stats(env: &Env, restaurant_id: u64, stats: &RestaurantStats) {
+        let key = DataKey::RestaurantStats(restaurant_id);
+        let ttl = Self::persistent_ttl(env);
+        env.storage().persistent().set(&key, stats);
+        env.storage().persistent().extend_ttl(&key, ttl, ttl);
+    }
+
+    /// Increment the customer's delivered-order count, returning the
+    /// previous value.
+    fn bump_delivered_count(env: &Env, customer: &Address) -> u32 {
+        let key = DataKey::CustomerDelivered(customer.clone());
+        let count: u32 = env.storage().persistent().get(&key).unwrap_or(0);
+        let ttl = Self::persistent_ttl(env);
+        env.storage().persistent().set(&key, &(count + 1));
+        env.storage().persistent().extend_ttl(&key, ttl, ttl);
+        count
+    }
+
+    /// Mint the delivery reward for `order`, unless rewards are disabled or
+    /// the customer is still inside the reward cooldown.  The first-order
+    /// bonus is added to the customer's first delivery made while rewards
+    /// are enabled.  In claim mode the reward is accrued to `PendingReward`
+    /// instead of minted.  Returns the amount rewarded, or `None` if the
+    /// reward was skipped or the mint failed.
+    fn maybe_mint_reward(env: &Env, order: &Order) -> Option<i128> {
+        let token: Address = env.storage().instance().get(&DataKey::LoyaltyToken)?;
+        let first_delivery = Self::bump_delivered_count(env, &order.customer) == 0;
+        let amount = Self::reward_amount(env, order, first_delivery);
+        if amount <= 0 {
+            return None;
+        }
+
+        if Self::in_reward_cooldown(env, &order.customer) {
+            if Self::verbose_events(env) {
+                let data = (order.id, order.customer.clone());
+                Self::emit(env, Symbol::new(env, "rewardskip"), data);
+            }
+            return None;
+        }
+
+        Self::mint_reward(env, &token, order, amount)
+    }
+
+    /// Pay `amount` to the order's customer, after netting off reward debt,
+    /// by minting or, in claim mode, accruing it.  Returns the amount
+    /// actually rewarded, `0` if the debt absorbed all of it, or `None` if
+    /// the token rejected the mint, in which case nothing is recorded.
+    fn mint_reward(env: &Env, token: &Address, order: &Order, amount: i128) -> Option<i128> {
+        let now = env.ledger().timestamp();
+        let ttl = Self::persistent_ttl(env);
+        let claim_mode: bool = env
+            .storage()
+            .instance()
+            .get(&DataKey::ClaimMode)
+            .unwrap_or(false);
+
+        // Net off any reward debt left by an earlier clawback.
+        let debt_key = DataKey::RewardDebt(order.customer.clone());
+        let debt: i128 = env.storage().persistent().get(&debt_key).unwrap_or(0);
+        let offset = debt.min(amount);
+        let amount = amount - offset;
+
+        if amount > 0 && !claim_mode {
+            let lock_secs: u64 = env
+                .storage()
+                .instance()
+                .get(&DataKey::PromoLockSecs)
+                .unwrap_or(0);
+            let loyalty = LoyaltyClient::new(env, token);
+            let minter = env.current_contract_address();
+            let minted = if lock_secs > 0 && Self::active_promo(env).is_some() {
+                let unlock_at = now.saturating_add(lock_secs);
+                loyalty
+                    .try_mint_locked(&minter, &order.customer, &amount, &unlock_at)
+                    .is_ok()
+            } else {
+                loyalty.try_mint(&minter, &order.customer, &amount).is_ok()
+            };
+            if !minted {
+                return None;
+            }
+        }
+
+        if offset > 0 {
+            if debt > offset {
+                env.storage().persistent().set(&debt_key, &(debt - offset));
+            } else {
+                env.storage().persistent().remove(&debt_key);
+            }
+        }
+        // A reward swallowed by debt still counts for the cooldown.
+        let last_key = DataKey::LastReward(order.customer.clone());
+        env.storage().persistent().set(&last_key, &now);
+        env.storage().persistent().extend_ttl(&last_key, ttl, ttl);
+        if amount <= 0 {
+            return Some(0);
+        }
+        let reward_key = DataKey::OrderReward(order.id);
+        env.storage().persistent().set(&reward_key, &amount);
+        env.storage().persistent().extend_ttl(&reward_key, ttl, ttl);
+        Self::update_customer_stats(env, &order.customer, |c| c.bite_earned += amount);
+        if claim_mode {
+            let pending_key = DataKey::PendingReward(order.customer.clone());
+            let pending: i128 = env.storage().persistent().get(&pending_key).unwrap_or(0);
+            env.storage().persistent().set(&pending_key, &(pending + amount));
+            env.storage().persistent().extend_ttl(&pending_key, ttl, ttl);
+        }
+
+        if Self::verbose_events(env) {
+            let name = if claim_mode {
+                symbol_short!("accrued")
+            } else {
+                symbol_short!("rewarded")
+            };
+            Self::emit(env, name, (order.id, order.customer.clone(), amount));
+        }
+        Some(amount)
+    }
+
+    /// Take back the reward recorded for `order`; see `refund_dispute`.
+    fn clawback_reward(env: &Env, order: &Order) {
+        let reward_key = DataKey::OrderReward(order.id);
+        let reward: i128 = env.storage().persistent().get(&reward_key).unwrap_or(0);
+        if reward <= 0 {
+            return;
+        }
+        env.storage().persistent().remove(&reward_key);
+        Self::update_customer_stats(env, &order.customer, |c| c.bite_earned -= reward);
+        let ttl = Self::persistent_ttl(env);
+
+        let pending_key = DataKey::PendingReward(order.customer.clone());
+        let pending: i128 = env.storage().persistent().get(&pending_key).unwrap_or(0);
+        let from_pending = pending.min(reward);
+        if from_pending > 0 {
+            env.storage().persistent().set(&pending_key, &(pending - from_pending));
+            env.storage().persistent().extend_ttl(&pending_key, ttl, ttl);
+        }
+
+        let mut remaining = reward - from_pending;
+        let mut burned: i128 = 0;
+        if remaining > 0 {
+            let token: Option<Address> = env.storage().instance().get(&DataKey::LoyaltyToken);
+            if let Some(token) = token {
+                burned = LoyaltyClient::new(env, &token).clawback(
+                    &env.current_contract_address(),
+                    &order.customer,
+                    &remaining,
+                );
+                remaining -= burned;
+            }
+        }
+        if remaining > 0 {
+            let debt_key = DataKey::RewardDebt(order.customer.clone());
+            let debt: i128 = env.storage().persistent().get(&debt_key).unwrap_or(0);
+            env.storage().persistent().set(&debt_key, &(debt + remaining));
+            env.storage().persistent().extend_ttl(&debt_key, ttl, ttl);
+        }
+
+        let data = (order.id, order.customer.clone(), from_pending + burned, remaining);
+        Self::emit(env, symbol_short!("clawback"), data);
+    }
+
+    /// Publish an order event whose payload is `(seq, data)`, where `seq`
+    /// is the next value of the contract-wide event sequence.
+    fn emit<T: IntoVal<Env, Val>>(env: &Env, name: Symbol, data: T) {
+        let seq: u64 = env
+            .storage()
+            .instance()
+            .get(&DataKey::EventSeq)
+            .unwrap_or(0)
+            + 1;
+        env.storage().instance().set(&DataKey::EventSeq, &seq);
+        let data: Val = data.into_val(env);
+        env.events()
+            .publish((name, symbol_short!("order")), (seq, data));
+    }
+
+    fn verbose_events(env: &Env) -> bool {
+        env.storage()
+            .instance()
+            .get(&DataKey::VerboseEvents)
+            .unwrap_or(true)
+    }
+
+    fn append_to_list(env: &Env, key: DataKey, id: u64, ttl: u32) {
+        let mut list: Vec<u64> = env
+            .storage()
+            .persistent()
+            .get(&key)
+            .unwrap_or_else(|| vec![env]);
+        list.push_back(id);
+        env.storage().persistent().set(&key, &list);
+        env.storage().persistent().extend_ttl(&key, ttl, ttl);
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Tests
+// ---------------------------------------------------------------------------
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use loyalty_token::{LoyaltyToken, LoyaltyTokenClient};
+    use soroban_sdk::testutils::{Address as _, Events, Ledger};
+    use soroban_sdk::{vec, Env, IntoVal, String};
+
+    fn make_item(env: &Env, id: u64, qty: u32, price: i128) -> OrderItem {
+        OrderItem {
+            menu_item_id: id,
+            name: String::from_str(env, "Jollof Rice"),
+            quantity: qty,
+            unit_price: price,
+        }
+    }
+
+    fn setup() -> (Env, OrderContractClient<'static>) {
+        let env = Env::default();
+        env.mock_all_auths();
+        let cid = env.register_contract(None, OrderContract);
+        let client = OrderContractClient::new(&env, &cid);
+        (env, client)
+    }
+
+    /// Register a BITE token whose minter is the order contract and enable
+    /// rewards at `rate_bps`.
+    fn setup_rewards(
+        env: &Env,
+        client: &OrderContractClient<'static>,
+        admin: &Address,
+        rate_bps: u32,
+    ) -> LoyaltyTokenClient<'static> {
+        let token_id = env.register_contract(None, LoyaltyToken);
+        let token = LoyaltyTokenClient::new(env, &token_id);
+        token.initialize(admin, &client.address);
+        client.set_loyalty_token(admin, &token_id);
+        client.set_reward_rate(admin, &rate_bps);
+        token
+    }
+
+    /// Advance a freshly placed order all the way to `Delivered`.
+    fn deliver(client: &OrderContractClient<'static>, admin: &Address, order_id: u64) {
+        for _ in 0..4 {
+            client.advance_status(admin, &order_id);
+        }
+    }
+
+    #[test]
+    fn test_place_and_get_order() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+
+        client.initialize(&admin);
+
+        let items = vec![&env, make_item(&env, 1, 2, 5_000_000)]; // 2 × 0.5 XLM
+        let id = client.place_order(
+            &customer,
+            &42,
+            &items,
+            &String::from_str(&env, "No onions please"),
+        );
+
+        assert_eq!(id, 1);
+        let order = client.get_order(&id);
+        assert_eq!(order.total_amount, 10_000_000);
+        assert_eq!(order.status, OrderStatus::Pending);
+    }
+
+    #[test]
+    fn test_advance_status() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+
+        let items = vec![&env, make_item(&env, 1, 1, 7_000_000)];
+        let id = client.place_order(&customer, &1, &items, &String::from_str(&env, ""));
+
+        client.advance_status(&admin, &id);
+        assert_eq!(client.get_order(&id).status, OrderStatus::Confirmed);
+
+        client.advance_status(&admin, &id);
+        assert_eq!(client.get_order(&id).status, OrderStatus::Preparing);
+
+        client.advance_status(&admin, &id);
+        assert_eq!(client.get_order(&id).status, OrderStatus::Ready);
+
+        client.advance_status(&admin, &id);
+        assert_eq!(client.get_order(&id).status, OrderStatus::Delivered);
+    }
+
+    #[test]
+    fn test_customer_cancel_pending() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+
+        let items = vec![&env, make_item(&env, 2, 1, 3_000_000)];
+        let id = client.place_order(&customer, &1, &items, &String::from_str(&env, ""));
+
+        client.cancel_order(&customer, &id);
+        assert_eq!(client.get_order(&id).status, OrderStatus::Cancelled);
+    }
+
+    #[test]
+    #[should_panic(expected = "order can no longer be cancelled by the customer")]
+    fn test_customer_cannot_cancel_confirmed() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+
+        let items = vec![&env, make_item(&env, 1, 1, 5_000_000)];
+        let id = client.place_order(&customer, &1, &items, &String::from_str(&env, ""));
+        client.advance_status(&admin, &id);
+        client.cancel_order(&customer, &id);
+    }
+
+    #[test]
+    fn test_get_restaurant_orders() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+
+        let items = vec![&env, make_item(&env, 1, 1, 5_000_000)];
+        client.place_order(&customer, &7, &items.clone(), &String::from_str(&env, ""));
+        client.place_order(&customer, &7, &items, &String::from_str(&env, ""));
+
+        let orders = client.get_restaurant_orders(&7);
+        assert_eq!(orders.len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "order disputed")]
+    fn test_disputed_order_cannot_advance() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+
+        let items = vec![&env, make_item(&env, 1, 1, 5_000_000)];
+        let id = client.place_order(&customer, &1, &items, &String::from_str(&env, ""));
+        client.advance_status(&admin, &id);
+
+        client.flag_dispute(&customer, &id);
+        assert!(client.get_order(&id).disputed);
+        client.advance_status(&admin, &id);
+    }
+
+    #[test]
+    fn test_clear_dispute_allows_advance() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+
+        let items = vec![&env, make_item(&env, 1, 1, 5_000_000)];
+        let id = client.place_order(&customer, &1, &items, &String::from_str(&env, ""));
+
+        client.flag_dispute(&customer, &id);
+        client.clear_dispute(&admin, &id);
+        assert!(!client.get_order(&id).disputed);
+
+        client.advance_status(&admin, &id);
+        assert_eq!(client.get_order(&id).status, OrderStatus::Confirmed);
+    }
+
+    #[test]
+    #[should_panic(expected = "unauthorized")]
+    fn test_only_customer_can_flag_dispute() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        let stranger = Address::generate(&env);
+        client.initialize(&admin);
+
+        let items = vec![&env, make_item(&env, 1, 1, 5_000_000)];
+        let id = client.place_order(&customer, &1, &items, &String::from_str(&env, ""));
+        client.flag_dispute(&stranger, &id);
+    }
+
+    #[test]
+    fn test_reward_cooldown() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+        let token = setup_rewards(&env, &client, &admin, 100); // 1 %
+        client.set_reward_cooldown(&admin, &3_600);
+
+        let items = vec![&env, make_item(&env, 1, 1, 10_000_000)];
+        let notes = String::from_str(&env, "");
+
+        // First delivery is rewarded.
+        let first = client.place_order(&customer, &1, &items, &notes);
+        deliver(&client, &admin, first);
+        assert_eq!(token.balance(&customer), 100_000);
+
+        // Second delivery inside the cooldown completes but earns nothing.
+        env.ledger().with_mut(|l| l.timestamp += 600);
+        let second = client.place_order(&customer, &1, &items, &notes);
+        deliver(&client, &admin, second);
+        assert_eq!(client.get_order(&second).status, OrderStatus::Delivered);
+        assert_eq!(token.balance(&customer), 100_000);
+
+        // Once the cooldown has elapsed rewards resume.
+        env.ledger().with_mut(|l| l.timestamp += 3_600);
+        let third = client.place_order(&customer, &1, &items, &notes);
+        deliver(&client, &admin, third);
+        assert_eq!(token.balance(&customer), 200_000);
+    }
+
+    #[test]
+    fn test_strict_pricing_accepts_menu_price() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+        client.set_menu_price(&admin, &1, &7, &5_000_000);
+        client.set_strict_pricing(&admin, &true);
+
+        let items = vec![&env, make_item(&env, 7, 2, 5_000_000)];
+        let id = client.place_order(&customer, &1, &items, &String::from_str(&env, ""));
+        assert_eq!(client.get_order(&id).total_amount, 10_000_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "stale price")]
+    fn test_strict_pricing_rejects_stale_price() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+        client.set_menu_price(&admin, &1, &7, &5_000_000);
+        client.set_strict_pricing(&admin, &true);
+
+        let items = vec![&env, make_item(&env, 7, 1, 4_000_000)];
+        client.place_order(&customer, &1, &items, &String::from_str(&env, ""));
+    }
+
+    #[test]
+    fn test_lenient_pricing_accepts_mismatch() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+        client.set_menu_price(&admin, &1, &7, &5_000_000);
+
+        let items = vec![&env, make_item(&env, 7, 1, 4_000_000)];
+        let id = client.place_order(&customer, &1, &items, &String::from_str(&env, ""));
+        assert_eq!(client.get_order(&id).total_amount, 4_000_000);
+    }
+
+    #[test]
+    fn test_reward_round_floor() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+        let token = setup_rewards(&env, &client, &admin, 1_000); // 10 %
+
+        // 10 % of 16 is 1.6 base units.
+        let items = vec![&env, make_item(&env, 1, 1, 16)];
+        let id = client.place_order(&customer, &1, &items, &String::from_str(&env, ""));
+        deliver(&client, &admin, id);
+        assert_eq!(token.balance(&customer), 1);
+    }
+
+    #[test]
+    fn test_reward_round_nearest() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+        let token = setup_rewards(&env, &client, &admin, 1_000);
+        client.set_round_mode(&admin, &RoundMode::Nearest);
+
+        let items = vec![&env, make_item(&env, 1, 1, 16)];
+        let id = client.place_order(&customer, &1, &items, &String::from_str(&env, ""));
+        deliver(&client, &admin, id);
+        assert_eq!(token.balance(&customer), 2);
+    }
+
+    #[test]
+    fn test_is_initialized() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+
+        assert!(!client.is_initialized());
+        client.initialize(&admin);
+        assert!(client.is_initialized());
+    }
+
+    #[test]
+    fn test_customer_cancel_within_grace() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+        client.set_cancel_grace(&admin, &300);
+
+        let items = vec![&env, make_item(&env, 1, 1, 5_000_000)];
+        let id = client.place_order(&customer, &1, &items, &String::from_str(&env, ""));
+        client.advance_status(&admin, &id);
+
+        env.ledger().with_mut(|l| l.timestamp += 300);
+        client.cancel_order(&customer, &id);
+        assert_eq!(client.get_order(&id).status, OrderStatus::Cancelled);
+    }
+
+    #[test]
+    #[should_panic(expected = "order can no longer be cancelled by the customer")]
+    fn test_customer_cancel_after_grace_panics() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+        client.set_cancel_grace(&admin, &300);
+
+        let items = vec![&env, make_item(&env, 1, 1, 5_000_000)];
+        let id = client.place_order(&customer, &1, &items, &String::from_str(&env, ""));
+        client.advance_status(&admin, &id);
+
+        env.ledger().with_mut(|l| l.timestamp += 301);
+        client.cancel_order(&customer, &id);
+    }
+
+    #[test]
+    fn test_customer_cancel_with_unbounded_grace() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+        client.set_cancel_grace(&admin, &u64::MAX);
+        env.ledger().with_mut(|l| l.timestamp = 1_000);
+
+        let items = vec![&env, make_item(&env, 1, 1, 5_000_000)];
+        let id = client.place_order(&customer, &1, &items, &String::from_str(&env, ""));
+        client.advance_status(&admin, &id);
+
+        env.ledger().with_mut(|l| l.timestamp += 1_000_000);
+        client.cancel_order(&customer, &id);
+        assert_eq!(client.get_order(&id).status, OrderStatus::Cancelled);
+    }
+
+    #[test]
+    fn test_get_orders_for_restaurants() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+
+        let items = vec![&env, make_item(&env, 1, 1, 5_000_000)];
+        let notes = String::from_str(&env, "");
+        let a1 = client.place_order(&customer, &1, &items, &notes);
+        let b1 = client.place_order(&customer, &2, &items, &notes);
+        let a2 = client.place_order(&customer, &1, &items, &notes);
+        let b2 = client.place_order(&customer, &2, &items, &notes);
+        client.place_order(&customer, &3, &items, &notes);
+
+        let all = client.get_orders_for_restaurants(&vec![&env, 1, 2], &10);
+        assert_eq!(all, vec![&env, a1, a2, b1, b2]);
+
+        let bounded = client.get_orders_for_restaurants(&vec![&env, 1, 2], &1);
+        assert_eq!(bounded, vec![&env, a1, b1]);
+    }
+
+    #[test]
+    fn test_first_order_bonus_fires_once() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+        let token = setup_rewards(&env, &client, &admin, 100); // 1 %
+        client.set_first_order_bonus(&admin, &5_000_000);
+
+        let items = vec![&env, make_item(&env, 1, 1, 10_000_000)];
+        let notes = String::from_str(&env, "");
+
+        let first = client.place_order(&customer, &1, &items, &notes);
+        deliver(&client, &admin, first);
+        assert_eq!(token.balance(&customer), 100_000 + 5_000_000);
+
+        let second = client.place_order(&customer, &1, &items, &notes);
+        deliver(&client, &admin, second);
+        assert_eq!(token.balance(&customer), 200_000 + 5_000_000);
+    }
+
+    #[test]
+    fn test_get_customer_rewards_proxies_token_balance() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+        assert_eq!(client.get_customer_rewards(&customer), 0);
+
+        let token = setup_rewards(&env, &client, &admin, 100); // 1 %
+        let items = vec![&env, make_item(&env, 1, 2, 5_000_000)];
+        let id = client.place_order(&customer, &1, &items, &String::from_str(&env, ""));
+        deliver(&client, &admin, id);
+
+        assert_eq!(client.get_customer_rewards(&customer), 100_000);
+        assert_eq!(client.get_customer_rewards(&customer), token.balance(&customer));
+    }
+
+    #[test]
+    fn test_quiet_mode_keeps_core_events() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+        setup_rewards(&env, &client, &admin, 100);
+        client.set_verbose_events(&admin, &false);
+
+        // Only the latest invocation's events are visible, so each core
+        // event is checked right after the call that emits it.
+        let has_event = |name: &str| {
+            let topics = (Symbol::new(&env, name), symbol_short!("order")).into_val(&env);
+            env.events()
+                .all()
+                .iter()
+                .any(|(contract, t, _)| contract == client.address && t == topics)
+        };
+        let items = vec![&env, make_item(&env, 1, 1, 10_000_000)];
+        let id = client.place_order(&customer, &1, &items, &String::from_str(&env, ""));
+        assert!(has_event("placed"));
+        for _ in 0..4 {
+            client.advance_status(&admin, &id);
+            assert!(has_event("advanced"));
+        }
+        // The final advance delivered the order without a verbose event.
+        assert!(has_event("completed"));
+        assert!(!has_event("rewarded"));
+    }
+
+    #[test]
+    fn test_preview_reward_matches_minted_amount() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+        let token = setup_rewards(&env, &client, &admin, 150); // 1.5 %
+        client.set_first_order_bonus(&admin, &2_000_000);
+        client.set_round_mode(&admin, &RoundMode::Ceil);
+
+        let items = vec![&env, make_item(&env, 1, 3, 3_333_333)];
+        let notes = String::from_str(&env, "");
+        let id = client.place_order(&customer, &1, &items, &notes);
+
+        let preview = client.preview_reward(&id);
+        assert!(preview > 0);
+        deliver(&client, &admin, id);
+        assert_eq!(token.balance(&customer), preview);
+        assert_eq!(client.preview_reward(&id), 0);
+    }
+
+    #[test]
+    fn test_promo_multiplier_applies_only_inside_window() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+        let token = setup_rewards(&env, &client, &admin, 100); // 1 %
+        client.set_promo(&admin, &20_000, &1_000, &2_000); // double BITE
+
+        let items = vec![&env, make_item(&env, 1, 1, 10_000_000)];
+        let notes = String::from_str(&env, "");
+
+        env.ledger().with_mut(|l| l.timestamp = 1_500);
+        let during = client.place_order(&customer, &1, &items, &notes);
+        deliver(&client, &admin, during);
+        assert_eq!(token.balance(&customer), 200_000);
+
+        env.ledger().with_mut(|l| l.timestamp = 2_000);
+        let after = client.place_order(&customer, &1, &items, &notes);
+        deliver(&client, &admin, after);
+        assert_eq!(token.balance(&customer), 300_000);
+    }
+
+    #[test]
+    fn test_event_seq_increments_per_event() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+        assert_eq!(client.current_event_seq(), 0);
+
+        let items = vec![&env, make_item(&env, 1, 1, 10_000_000)];
+        let notes = String::from_str(&env, "");
+        client.place_order(&customer, &1, &items, &notes);
+        assert_eq!(client.current_event_seq(), 1);
+        client.place_order(&customer, &1, &items, &notes);
+        let (_, _, data) = env.events().all().last().unwrap();
+        assert_eq!(client.current_event_seq(), 2);
+
+        let (seq, _payload): (u64, (u64, u64, Address, i128)) = data.into_val(&env);
+        assert_eq!(seq, 2);
+    }
+
+    #[test]
+    fn test_get_orders_global_spans_restaurants() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+
+        let items = vec![&env, make_item(&env, 1, 1, 10_000_000)];
+        let notes = String::from_str(&env, "");
+        for restaurant_id in [1u64, 2, 3, 1, 2] {
+            client.place_order(&customer, &restaurant_id, &items, &notes);
+        }
+
+        assert_eq!(client.get_orders_global(&1, &3), vec![&env, 1u64, 2, 3]);
+        assert_eq!(client.get_orders_global(&4, &3), vec![&env, 4u64, 5]);
+        assert_eq!(client.get_orders_global(&6, &3).len(), 0);
+    }
+
+    #[test]
+    fn test_restaurant_stats_follow_lifecycle() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+
+        let items = vec![&env, make_item(&env, 1, 2, 5_000_000)];
+        let notes = String::from_str(&env, "");
+        let delivered = client.place_order(&customer, &7, &items, &notes);
+        let cancelled = client.place_order(&customer, &7, &items, &notes);
+        client.place_order(&customer, &7, &items, &notes);
+        client.place_order(&customer, &8, &items, &notes);
+
+        deliver(&client, &admin, delivered);
+        client.cancel_order(&customer, &cancelled);
+
+        let stats = client.get_restaurant_stats(&7);
+        assert_eq!(stats.order_count, 3);
+        assert_eq!(stats.active_orders, 1);
+        assert_eq!(stats.total_revenue, 10_000_000);
+        assert_eq!(client.get_restaurant_stats(&8).active_orders, 1);
+    }
+
+    #[test]
+    fn test_merge_pending_orders() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+
+        let notes = String::from_str(&env, "");
+        let first = vec![&env, make_item(&env, 1, 2, 5_000_000)];
+        let second = vec![&env, make_item(&env, 2, 1, 3_000_000)];
+        let into = client.place_order(&customer, &1, &first, &notes);
+        let from = client.place_order(&customer, &1, &second, &notes);
+
+        client.merge_orders(&customer, &into, &from);
+
+        let merged = client.get_order(&into);
+        assert_eq!(merged.items.len(), 2);
+        assert_eq!(merged.total_amount, 13_000_000);
+        assert_eq!(client.get_order(&from).status, OrderStatus::Cancelled);
+        assert_eq!(client.get_restaurant_stats(&1).active_orders, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "orders belong to different restaurants")]
+    fn test_merge_rejects_different_restaurants() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+
+        let notes = String::from_str(&env, "");
+        let items = vec![&env, make_item(&env, 1, 1, 5_000_000)];
+        let into = client.place_order(&customer, &1, &items, &notes);
+        let from = client.place_order(&customer, &2, &items, &notes);
+        client.merge_orders(&customer, &into, &from);
+    }
+
+    #[test]
+    fn test_storage_ttl_config_applies_to_new_entries() {
+        use soroban_sdk::testutils::storage::Persistent as _;
+
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+        let items = vec![&env, make_item(&env, 1, 1, 10_000_000)];
+        let notes = String::from_str(&env, "");
+
+        let ttl_of = |id: u64| {
+            env.as_contract(&client.address, || {
+                env.storage().persistent().get_ttl(&DataKey::Order(id))
+            })
+        };
+
+        let before = client.place_order(&customer, &1, &items, &notes);
+        assert_eq!(ttl_of(before), 2_073_600);
+
+        client.set_storage_ttl(&admin, &3_000_000, &100_000);
+        let after = client.place_order(&customer, &1, &items, &notes);
+        assert_eq!(ttl_of(after), 3_000_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "ttl out of range")]
+    fn test_storage_ttl_below_minimum_rejected() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        client.initialize(&admin);
+        client.set_storage_ttl(&admin, &1_000, &17_280);
+    }
+
+    #[test]
+    fn test_close_delivered_order_after_window() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+        let token = setup_rewards(&env, &client, &admin, 100);
+        client.set_inspection_window(&admin, &3_600);
+
+        let items = vec![&env, make_item(&env, 1, 1, 10_000_000)];
+        let id = client.place_order(&customer, &1, &items, &String::from_str(&env, ""));
+        env.ledger().with_mut(|l| l.timestamp = 1_000);
+        deliver(&client, &admin, id);
+        let rewarded = token.balance(&customer);
+
+        env.ledger().with_mut(|l| l.timestamp = 4_600);
+        client.close_order(&admin, &id);
+
+        let order = client.get_order(&id);
+        assert_eq!(order.status, OrderStatus::Closed);
+        assert_eq!(order.delivered_at, 1_000);
+        assert_eq!(token.balance(&customer), rewarded);
+        assert_eq!(client.get_restaurant_stats(&1).total_revenue, 10_000_000);
+    }
+
+    #[test]
+    fn test_closed_order_cannot_be_reopened() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+        let items = vec![&env, make_item(&env, 1, 1, 10_000_000)];
+        let id = client.place_order(&customer, &1, &items, &String::from_str(&env, ""));
+        deliver(&client, &admin, id);
+        client.close_order(&admin, &id);
+
+        // Even an explicitly allowed transition cannot reopen it.
+        client.set_allowed_transition(&admin, &OrderStatus::Closed, &OrderStatus::Pending, &true);
+        assert!(client.try_set_status(&admin, &id, &OrderStatus::Pending).is_err());
+        assert!(client.try_set_status(&admin, &id, &OrderStatus::Cancelled).is_err());
+        assert_eq!(client.get_order(&id).status, OrderStatus::Closed);
+    }
+
+    #[test]
+    #[should_panic(expected = "inspection window still open")]
+    fn test_close_inside_inspection_window_rejected() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+        client.set_inspection_window(&admin, &3_600);
+
+        let items = vec![&env, make_item(&env, 1, 1, 10_000_000)];
+        let id = client.place_order(&customer, &1, &items, &String::from_str(&env, ""));
+        env.ledger().with_mut(|l| l.timestamp = 1_000);
+        deliver(&client, &admin, id);
+
+        env.ledger().with_mut(|l| l.timestamp = 4_599);
+        client.close_order(&admin, &id);
+    }
+
+    #[test]
+    #[should_panic(expected = "only delivered orders can be closed")]
+    fn test_close_undelivered_order_rejected() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+
+        let items = vec![&env, make_item(&env, 1, 1, 10_000_000)];
+        let id = client.place_order(&customer, &1, &items, &String::from_str(&env, ""));
+        client.close_order(&admin, &id);
+    }
+
+    #[test]
+    fn test_get_last_order_for_customer() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        let newcomer = Address::generate(&env);
+        client.initialize(&admin);
+
+        let notes = String::from_str(&env, "");
+        let items = vec![&env, make_item(&env, 1, 1, 5_000_000)];
+        client.place_order(&customer, &1, &items, &notes);
+        let latest = client.place_order(&customer, &2, &items, &notes);
+
+        assert_eq!(client.get_last_order_for_customer(&customer).unwrap().id, latest);
+        assert!(client.get_last_order_for_customer(&newcomer).is_none());
+    }
+
+    #[test]
+    fn test_configured_confirmed_cancellation() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+        let statuses = vec![&env, OrderStatus::Pending, OrderStatus::Confirmed];
+        client.set_customer_cancellable(&admin, &statuses);
+
+        let items = vec![&env, make_item(&env, 1, 1, 10_000_000)];
+        let id = client.place_order(&customer, &1, &items, &String::from_str(&env, ""));
+        client.advance_status(&admin, &id); // Confirmed
+        client.cancel_order(&customer, &id);
+        assert_eq!(client.get_order(&id).status, OrderStatus::Cancelled);
+    }
+
+    #[test]
+    fn test_configured_confirmed_respects_grace() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+        let statuses = vec![&env, OrderStatus::Pending, OrderStatus::Confirmed];
+        client.set_customer_cancellable(&admin, &statuses);
+        client.set_cancel_grace(&admin, &300);
+
+        let items = vec![&env, make_item(&env, 1, 1, 10_000_000)];
+        let notes = String::from_str(&env, "");
+        env.ledger().with_mut(|l| l.timestamp = 1_000);
+        let late = client.place_order(&customer, &1, &items, &notes);
+        client.advance_status(&admin, &late); // Confirmed at 1 000
+        let early = client.place_order(&customer, &1, &items, &notes);
+        env.ledger().with_mut(|l| l.timestamp = 1_200);
+        client.advance_status(&admin, &early); // Confirmed at 1 200
+
+        env.ledger().with_mut(|l| l.timestamp = 1_400);
+        assert!(client.try_cancel_order(&customer, &late).is_err());
+        client.cancel_order(&customer, &early);
+        assert_eq!(client.get_order(&early).status, OrderStatus::Cancelled);
+        assert_eq!(client.get_order(&late).status, OrderStatus::Confirmed);
+    }
+
+    #[test]
+    #[should_panic(expected = "order can no longer be cancelled by the customer")]
+    fn test_configured_cancellation_stops_before_preparing() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+        let statuses = vec![&env, OrderStatus::Pending, OrderStatus::Confirmed];
+        client.set_customer_cancellable(&admin, &statuses);
+
+        let items = vec![&env, make_item(&env, 1, 1, 10_000_000)];
+        let id = client.place_order(&customer, &1, &items, &String::from_str(&env, ""));
+        client.advance_status(&admin, &id); // Confirmed
+        client.advance_status(&admin, &id); // Preparing
+        client.cancel_order(&customer, &id);
+    }
+
+    #[test]
+    fn test_claim_mode_accrues_then_mints_sum() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+        let token = setup_rewards(&env, &client, &admin, 100); // 1 %
+        client.set_claim_mode(&admin, &true);
+
+        let notes = String::from_str(&env, "");
+        let small = vec![&env, make_item(&env, 1, 1, 10_000_000)];
+        let large = vec![&env, make_item(&env, 2, 1, 20_000_000)];
+        let first = client.place_order(&customer, &1, &small, &notes);
+        let second = client.place_order(&customer, &1, &large, &notes);
+        deliver(&client, &admin, first);
+        deliver(&client, &admin, second);
+
+        assert_eq!(token.balance(&customer), 0);
+        assert_eq!(client.get_pending_reward(&customer), 300_000);
+
+        assert_eq!(client.claim_rewards(&customer), 300_000);
+        assert_eq!(token.balance(&customer), 300_000);
+        assert_eq!(client.get_pending_reward(&customer), 0);
+    }
+
+    #[test]
+    fn test_global_summary_counters() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+
+        let notes = String::from_str(&env, "");
+        let items = vec![&env, make_item(&env, 1, 1, 10_000_000)];
+        let delivered = client.place_order(&customer, &1, &items, &notes);
+        let cancelled = client.place_order(&customer, &2, &items, &notes);
+        client.place_order(&customer, &3, &items, &notes);
+
+        deliver(&client, &admin, delivered);
+        client.cancel_order(&customer, &cancelled);
+
+        let summary = client.get_global_summary();
+        assert_eq!(summary.total_orders, 3);
+        assert_eq!(summary.total_delivered, 1);
+        assert_eq!(summary.total_cancelled, 1);
+        assert_eq!(summary.total_volume, 10_000_000);
+    }
+
+    #[test]
+    fn test_place_order_and_escrow_in_one_call() {
+        use soroban_sdk::token;
+
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        let restaurant_wallet = Address::generate(&env);
+        client.initialize(&admin);
+
+        let payment_id = env.register_contract(None, payment::PaymentContract);
+        let payments = payment::PaymentContractClient::new(&env, &payment_id);
+        payments.initialize(&admin, &admin, &0);
+        payments.set_order_contract(&admin, &client.address);
+        client.set_payment_contract(&admin, &payment_id);
+
+        let sac = env.register_stellar_asset_contract_v2(admin.clone());
+        let token_addr = sac.address();
+        token::StellarAssetClient::new(&env, &token_addr).mint(&customer, &20_000_000);
+        let token_client = token::Client::new(&env, &token_addr);
+        token_client.approve(&customer, &payment_id, &20_000_000, &1_000);
+
+        let items = vec![&env, make_item(&env, 1, 2, 5_000_000)];
+        let id = client.place_order_and_escrow(
+            &customer,
+            &1,
+            &items,
+            &String::from_str(&env, ""),
+            &restaurant_wallet,
+            &token_addr,
+        );
+
+        assert_eq!(client.get_order(&id).status, OrderStatus::Pending);
+        let escrow = payments.get_payment(&id);
+        assert_eq!(escrow.amount, 10_000_000);
+        assert_eq!(escrow.payer, customer);
+        assert_eq!(token_client.balance(&customer), 10_000_000);
+        assert_eq!(token_client.balance(&payment_id), 10_000_000);
+    }
+
+    #[test]
+    fn test_encrypted_notes_round_trip() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+
+        let items = vec![&env, make_item(&env, 1, 1, 5_000_000)];
+        let notes_ref = String::from_str(&env, "ipfs://bafy-encrypted-notes");
+        let secret = client.place_order_encrypted(&customer, &1, &items, &notes_ref);
+        let plain = client.place_order(&customer, &1, &items, &String::from_str(&env, "Gate 4"));
+
+        let order = client.get_order(&secret);
+        assert!(order.notes_encrypted);
+        assert_eq!(order.notes_ref, notes_ref);
+        assert_eq!(order.notes, String::from_str(&env, ""));
+
+        let order = client.get_order(&plain);
+        assert!(!order.notes_encrypted);
+        assert_eq!(order.notes_ref, String::from_str(&env, ""));
+        assert_eq!(order.notes, String::from_str(&env, "Gate 4"));
+    }
+
+    #[test]
+    fn test_average_order_value_counts_delivered_only() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+        assert_eq!(client.get_average_order_value(&1), 0);
+
+        let notes = String::from_str(&env, "");
+        let small = vec![&env, make_item(&env, 1, 1, 10_000_000)];
+        let large = vec![&env, make_item(&env, 2, 2, 15_000_000)];
+        let first = client.place_order(&customer, &1, &small, &notes);
+        let second = client.place_order(&customer, &1, &large, &notes);
+        let cancelled = client.place_order(&customer, &1, &large, &notes);
+        deliver(&client, &admin, first);
+        deliver(&client, &admin, second);
+        client.cancel_order(&customer, &cancelled);
+
+        assert_eq!(client.get_average_order_value(&1), 20_000_000);
+    }
+
+    #[test]
+    fn test_validate_order_returns_total_without_storing() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        client.initialize(&admin);
+        let items = vec![
+            &env,
+            make_item(&env, 1, 2, 5_000_000),
+            make_item(&env, 2, 1, 3_000_000),
+        ];
+        let events_before = env.events().all().len();
+
+        let total = client.validate_order(&1, &items, &String::from_str(&env, ""));
+        assert_eq!(total, 13_000_000);
+        assert_eq!(client.get_count(), 0);
+        assert_eq!(env.events().all().len(), events_before);
+    }
+
+    #[test]
+    fn test_validate_order_rejects_what_place_order_rejects() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        client.initialize(&admin);
+        let notes = String::from_str(&env, "");
+
+        let empty: Vec<OrderItem> = Vec::new(&env);
+        assert!(client.try_validate_order(&1, &empty, &notes).is_err());
+        let zero_qty = vec![&env, make_item(&env, 1, 0, 5_000_000)];
+        assert!(client.try_validate_order(&1, &zero_qty, &notes).is_err());
+
+        client.set_menu_price(&admin, &1, &7, &5_000_000);
+        client.set_strict_pricing(&admin, &true);
+        let stale = vec![&env, make_item(&env, 7, 1, 4_000_000)];
+        assert!(client.try_validate_order(&1, &stale, &notes).is_err());
+        let fresh = vec![&env, make_item(&env, 7, 1, 5_000_000)];
+        assert_eq!(client.validate_order(&1, &fresh, &notes), 5_000_000);
+    }
+
+    #[test]
+    fn test_multi_order_links_two_restaurants() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+        let notes = String::from_str(&env, "");
+        let cart = vec![
+            &env,
+            (1u64, vec![&env, make_item(&env, 1, 1, 5_000_000)], notes.clone()),
+            (2u64, vec![&env, make_item(&env, 9, 2, 3_000_000)], notes.clone()),
+        ];
+
+        let ids = client.place_multi_order(&customer, &cart);
+        assert_eq!(ids.len(), 2);
+        let first = client.get_order(&ids.get(0).unwrap());
+        let second = client.get_order(&ids.get(1).unwrap());
+        assert_eq!((first.restaurant_id, first.total_amount), (1, 5_000_000));
+        assert_eq!((second.restaurant_id, second.total_amount), (2, 6_000_000));
+
+        let cart_id = client.get_order_cart(&first.id).unwrap();
+        assert_eq!(client.get_order_cart(&second.id), Some(cart_id));
+        assert_eq!(client.get_cart_orders(&cart_id), ids);
+
+        let items = vec![&env, make_item(&env, 1, 1, 1)];
+        let single = client.place_order(&customer, &1, &items, &notes);
+        assert_eq!(client.get_order_cart(&single), None);
+    }
+
+    #[test]
+    fn test_unapproved_restaurant_rejects_orders() {
+        use restaurant_registry::{RestaurantRegistry, RestaurantRegistryClient};
+
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let owner = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+        let registry_id = env.register_contract(None, RestaurantRegistry);
+        let registry = RestaurantRegistryClient::new(&env, &registry_id);
+        registry.initialize(&admin);
+        registry.set_approval_required(&admin, &true);
+        client.set_registry_contract(&admin, &registry_id);
+
+        let rid = registry.register_restaurant(
+            &owner,
+            &String::from_str(&env, "Test Rest"),
+            &String::from_str(&env, "test-rest"),
+        );
+        let items = vec![&env, make_item(&env, 1, 1, 5_000_000)];
+        let notes = String::from_str(&env, "");
+        assert!(client.try_place_order(&customer, &rid, &items, &notes).is_err());
+        assert!(client.try_validate_order(&rid, &items, &notes).is_err());
+
+        registry.approve_restaurant(&admin, &rid);
+        let id = client.place_order(&customer, &rid, &items, &notes);
+        assert_eq!(client.get_order(&id).restaurant_id, rid);
+    }
+
+    #[test]
+    fn test_order_timeline_records_each_transition() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+        env.ledger().with_mut(|l| l.timestamp = 1_000);
+
+        let items = vec![&env, make_item(&env, 1, 1, 7_000_000)];
+        let id = client.place_order(&customer, &1, &items, &String::from_str(&env, ""));
+        for _ in 0..4 {
+            env.ledger().with_mut(|l| l.timestamp += 60);
+            client.advance_status(&admin, &id);
         }
+
+        let expected = vec![
+            &env,
+            (OrderStatus::Pending, 1_000u64),
+            (OrderStatus::Confirmed, 1_060),
+            (OrderStatus::Preparing, 1_120),
+            (OrderStatus::Ready, 1_180),
+            (OrderStatus::Delivered, 1_240),
+        ];
+        assert_eq!(client.get_order_timeline(&id), expected);
     }
 
-    fn append_to_list(env: &Env, key: DataKey, id: u64, ttl: u32) {
-        let mut list: Vec<u64> = env
-            .storage()
-            .persistent()
-            .get(&key)
-            .unwrap_or_else(|| vec![env]);
-        list.push_back(id);
-        env.storage().persistent().set(&key, &list);
-        env.storage().persistent().extend_ttl(&key, ttl, ttl);
+    #[test]
+    fn test_promo_rewards_locked_until_unlock() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        let friend = Address::generate(&env);
+        client.initialize(&admin);
+        let token = setup_rewards(&env, &client, &admin, 100); // 1 %
+        client.set_promo(&admin, &20_000, &1_000, &2_000);
+        client.set_promo_lock(&admin, &86_400);
+
+        let items = vec![&env, make_item(&env, 1, 1, 10_000_000)];
+        let notes = String::from_str(&env, "");
+
+        env.ledger().with_mut(|l| l.timestamp = 1_500);
+        let during = client.place_order(&customer, &1, &items, &notes);
+        deliver(&client, &admin, during);
+        assert_eq!(token.balance(&customer), 200_000);
+        assert_eq!(token.locked_balance(&customer), 200_000);
+        assert!(token.try_transfer(&customer, &friend, &1).is_err());
+
+        // Rewards outside the promo stay liquid.
+        env.ledger().with_mut(|l| l.timestamp = 2_000);
+        let after = client.place_order(&customer, &1, &items, &notes);
+        deliver(&client, &admin, after);
+        assert_eq!(token.spendable_balance(&customer), 100_000);
+
+        env.ledger().with_mut(|l| l.timestamp = 1_500 + 86_400);
+        assert_eq!(token.locked_balance(&customer), 0);
+        token.transfer(&customer, &friend, &300_000);
+        assert_eq!(token.balance(&friend), 300_000);
     }
-}
 
-// ---------------------------------------------------------------------------
-// Tests
-// ---------------------------------------------------------------------------
+    #[test]
+    fn test_staff_create_order_for_customer() {
+        use restaurant_registry::{RestaurantRegistry, RestaurantRegistryClient};
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    use soroban_sdk::testutils::Address as _;
-    use soroban_sdk::{vec, Env, String};
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let owner = Address::generate(&env);
+        let stranger = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+        let registry_id = env.register_contract(None, RestaurantRegistry);
+        let registry = RestaurantRegistryClient::new(&env, &registry_id);
+        registry.initialize(&admin);
+        client.set_registry_contract(&admin, &registry_id);
+        let rid = registry.register_restaurant(
+            &owner,
+            &String::from_str(&env, "Test Rest"),
+            &String::from_str(&env, "test-rest"),
+        );
+        let items = vec![&env, make_item(&env, 1, 2, 4_000_000)];
+        let notes = String::from_str(&env, "phone order");
 
-    fn make_item(env: &Env, id: u64, qty: u32, price: i128) -> OrderItem {
-        OrderItem {
-            menu_item_id: id,
-            name: String::from_str(env, "Jollof Rice"),
-            quantity: qty,
-            unit_price: price,
-        }
+        let id = client.create_order_for_customer(&owner, &customer, &rid, &items, &notes);
+        let order = client.get_order(&id);
+        assert_eq!(order.customer, customer);
+        assert_eq!(order.status, OrderStatus::Pending);
+        assert_eq!(order.total_amount, 8_000_000);
+        assert_eq!(client.get_customer_orders(&customer), vec![&env, id]);
+
+        client.create_order_for_customer(&admin, &customer, &rid, &items, &notes);
+        let res = client.try_create_order_for_customer(&stranger, &customer, &rid, &items, &notes);
+        assert!(res.is_err());
     }
 
-    fn setup() -> (Env, OrderContractClient<'static>) {
-        let env = Env::default();
-        env.mock_all_auths();
-        let cid = env.register_contract(None, OrderContract);
-        let client = OrderContractClient::new(&env, &cid);
-        (env, client)
+    #[test]
+    fn test_auto_confirm_after_window() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+        env.ledger().with_mut(|l| l.timestamp = 1_000);
+
+        let items = vec![&env, make_item(&env, 1, 1, 5_000_000)];
+        let id = client.place_order(&customer, &1, &items, &String::from_str(&env, ""));
+        assert!(client.try_auto_confirm(&id).is_err()); // disabled by default
+
+        client.set_auto_confirm(&admin, &1, &600);
+        env.ledger().with_mut(|l| l.timestamp = 1_599);
+        assert!(client.try_auto_confirm(&id).is_err());
+
+        env.ledger().with_mut(|l| l.timestamp = 1_600);
+        client.auto_confirm(&id);
+        let order = client.get_order(&id);
+        assert_eq!(order.status, OrderStatus::Confirmed);
+        assert_eq!(order.confirmed_at, 1_600);
+        assert!(client.try_auto_confirm(&id).is_err()); // no longer pending
     }
 
     #[test]
-    fn test_place_and_get_order() {
+    fn test_blocked_customer_cannot_order() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+        let items = vec![&env, make_item(&env, 1, 1, 5_000_000)];
+        let notes = String::from_str(&env, "");
+
+        client.set_blocked(&admin, &1, &customer, &true);
+        assert!(client.is_blocked(&1, &customer));
+        assert!(client.try_place_order(&customer, &1, &items, &notes).is_err());
+        // The block is per restaurant.
+        client.place_order(&customer, &2, &items, &notes);
+
+        client.set_blocked(&admin, &1, &customer, &false);
+        assert!(!client.is_blocked(&1, &customer));
+        let id = client.place_order(&customer, &1, &items, &notes);
+        assert_eq!(client.get_order(&id).restaurant_id, 1);
+    }
+
+    #[test]
+    fn test_notes_policy_rejects_long_notes() {
         let (env, client) = setup();
         let admin = Address::generate(&env);
         let customer = Address::generate(&env);
+        client.initialize(&admin);
+        client.set_notes_policy(&admin, &10, &false);
+        let items = vec![&env, make_item(&env, 1, 1, 5_000_000)];
+
+        client.place_order(&customer, &1, &items, &String::from_str(&env, "no onions"));
+        let long = String::from_str(&env, "no onions please");
+        assert!(client.try_place_order(&customer, &1, &items, &long).is_err());
+        assert!(client.try_validate_order(&1, &items, &long).is_err());
+    }
 
+    #[test]
+    fn test_notes_policy_rejects_control_bytes() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
         client.initialize(&admin);
+        client.set_notes_policy(&admin, &0, &true);
+        let items = vec![&env, make_item(&env, 1, 1, 5_000_000)];
 
-        let items = vec![&env, make_item(&env, 1, 2, 5_000_000)]; // 2 × 0.5 XLM
-        let id = client.place_order(
-            &customer,
-            &42,
-            &items,
-            &String::from_str(&env, "No onions please"),
-        );
+        let multiline = String::from_str(&env, "gate code 42\nring twice");
+        client.place_order(&customer, &1, &items, &multiline);
+        let bell = String::from_bytes(&env, b"ring\x07");
+        assert!(client.try_place_order(&customer, &1, &items, &bell).is_err());
+    }
+
+    #[test]
+    fn test_customer_orders_at_restaurant() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        let other = Address::generate(&env);
+        client.initialize(&admin);
+        let items = vec![&env, make_item(&env, 1, 1, 5_000_000)];
+        let notes = String::from_str(&env, "");
+
+        let a1 = client.place_order(&customer, &1, &items, &notes);
+        client.place_order(&customer, &2, &items, &notes);
+        client.place_order(&other, &1, &items, &notes);
+        let a2 = client.place_order(&customer, &1, &items, &notes);
+
+        let at_one = client.get_customer_restaurant_orders(&customer, &1, &10);
+        assert_eq!(at_one, vec![&env, a1, a2]);
+        let first_only = client.get_customer_restaurant_orders(&customer, &1, &1);
+        assert_eq!(first_only, vec![&env, a1]);
+        let none = client.get_customer_restaurant_orders(&customer, &3, &10);
+        assert_eq!(none.len(), 0);
+    }
+
+    #[test]
+    fn test_refund_dispute_claws_back_reward() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+        let token = setup_rewards(&env, &client, &admin, 100); // 1 %
+        let items = vec![&env, make_item(&env, 1, 1, 10_000_000)];
+        let id = client.place_order(&customer, &1, &items, &String::from_str(&env, ""));
+        deliver(&client, &admin, id);
+        assert_eq!(token.balance(&customer), 100_000);
+
+        client.flag_dispute(&customer, &id);
+        assert!(client.try_close_order(&admin, &id).is_err());
+        client.refund_dispute(&admin, &id);
 
-        assert_eq!(id, 1);
         let order = client.get_order(&id);
-        assert_eq!(order.total_amount, 10_000_000);
-        assert_eq!(order.status, OrderStatus::Pending);
+        assert_eq!(order.status, OrderStatus::Cancelled);
+        assert!(!order.disputed);
+        assert_eq!(client.get_cancel_reason(&id), Some(CancelReason::Dispute));
+        assert_eq!(token.balance(&customer), 0);
+        assert_eq!(client.get_reward_debt(&customer), 0);
+        assert_eq!(client.get_restaurant_stats(&1).delivered_orders, 0);
     }
 
     #[test]
-    fn test_advance_status() {
+    fn test_clawback_shortfall_becomes_reward_debt() {
         let (env, client) = setup();
         let admin = Address::generate(&env);
         let customer = Address::generate(&env);
+        let friend = Address::generate(&env);
         client.initialize(&admin);
+        let token = setup_rewards(&env, &client, &admin, 100); // 1 %
+        let items = vec![&env, make_item(&env, 1, 1, 10_000_000)];
+        let notes = String::from_str(&env, "");
+        let first = client.place_order(&customer, &1, &items, &notes);
+        deliver(&client, &admin, first);
+        token.transfer(&customer, &friend, &60_000);
+
+        // Only the 40 000 still held can be burned.
+        client.flag_dispute(&customer, &first);
+        client.refund_dispute(&admin, &first);
+        assert_eq!(token.balance(&customer), 0);
+        assert_eq!(client.get_reward_debt(&customer), 60_000);
+
+        // The next reward pays the debt off first.
+        let second = client.place_order(&customer, &1, &items, &notes);
+        deliver(&client, &admin, second);
+        assert_eq!(token.balance(&customer), 40_000);
+        assert_eq!(client.get_reward_debt(&customer), 0);
+    }
 
-        let items = vec![&env, make_item(&env, 1, 1, 7_000_000)];
+    #[test]
+    fn test_completed_event_fires_once_on_delivery() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+        setup_rewards(&env, &client, &admin, 100); // 1 %
+
+        let items = vec![&env, make_item(&env, 1, 1, 10_000_000)];
         let id = client.place_order(&customer, &1, &items, &String::from_str(&env, ""));
+        deliver(&client, &admin, id);
 
-        client.advance_status(&admin, &id);
-        assert_eq!(client.get_order(&id).status, OrderStatus::Confirmed);
+        let topics = (symbol_short!("completed"), symbol_short!("order")).into_val(&env);
+        let is_completed = |(contract, t, _): &(Address, Vec<Val>, Val)| {
+            *contract == client.address && *t == topics
+        };
+        let events = env.events().all();
+        assert_eq!(events.iter().filter(is_completed).count(), 1);
+        let (_, _, payload) = events.iter().find(is_completed).unwrap();
+        let (_seq, data): (u64, (u64, Address, i128, i128)) = payload.into_val(&env);
+        assert_eq!(data, (id, customer, 10_000_000, 100_000));
+    }
 
-        client.advance_status(&admin, &id);
-        assert_eq!(client.get_order(&id).status, OrderStatus::Preparing);
+    #[test]
+    fn test_restaurant_reward_rate_override() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let alice = Address::generate(&env);
+        let bob = Address::generate(&env);
+        client.initialize(&admin);
+        let token = setup_rewards(&env, &client, &admin, 100); // 1 %
+        assert!(client.try_set_restaurant_reward_rate(&admin, &2, &300).is_err());
+        client.set_max_restaurant_reward_rate(&admin, &300);
+        client.set_restaurant_reward_rate(&admin, &2, &300);
+        assert!(client.try_set_restaurant_reward_rate(&admin, &2, &301).is_err());
+
+        let items = vec![&env, make_item(&env, 1, 1, 10_000_000)];
+        let notes = String::from_str(&env, "");
+        let default_order = client.place_order(&alice, &1, &items, &notes);
+        let premium_order = client.place_order(&bob, &2, &items, &notes);
+        deliver(&client, &admin, default_order);
+        deliver(&client, &admin, premium_order);
+        assert_eq!(token.balance(&alice), 100_000);
+        assert_eq!(token.balance(&bob), 300_000);
+
+        // Lowering the cap also limits the existing override.
+        client.set_max_restaurant_reward_rate(&admin, &200);
+        let capped_order = client.place_order(&bob, &2, &items, &notes);
+        deliver(&client, &admin, capped_order);
+        assert_eq!(token.balance(&bob), 500_000);
+    }
 
-        client.advance_status(&admin, &id);
-        assert_eq!(client.get_order(&id).status, OrderStatus::Ready);
+    #[test]
+    fn test_customer_stats_aggregate() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+        setup_rewards(&env, &client, &admin, 100); // 1 %
+        let notes = String::from_str(&env, "");
+        let small = vec![&env, make_item(&env, 1, 1, 10_000_000)];
+        let large = vec![&env, make_item(&env, 2, 1, 20_000_000)];
+
+        let a = client.place_order(&customer, &1, &small, &notes);
+        let b = client.place_order(&customer, &2, &large, &notes);
+        client.place_order(&customer, &2, &small, &notes); // still pending
+        deliver(&client, &admin, a);
+        deliver(&client, &admin, b);
+
+        let stats = client.get_customer_stats(&customer);
+        assert_eq!(stats.order_count, 3);
+        assert_eq!(stats.total_spent, 30_000_000);
+        assert_eq!((stats.favorite_restaurant, stats.favorite_orders), (2, 2));
+        assert_eq!(stats.bite_earned, 300_000);
+    }
 
-        client.advance_status(&admin, &id);
-        assert_eq!(client.get_order(&id).status, OrderStatus::Delivered);
+    #[test]
+    fn test_cancel_reason_recorded_and_emitted() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+        let items = vec![&env, make_item(&env, 1, 1, 5_000_000)];
+        let notes = String::from_str(&env, "");
+        let topics = (symbol_short!("cancelled"), symbol_short!("order")).into_val(&env);
+
+        let reasons = [
+            CancelReason::CustomerChanged,
+            CancelReason::RestaurantRejected,
+            CancelReason::OutOfStock,
+            CancelReason::Timeout,
+            CancelReason::Dispute,
+            CancelReason::Other,
+        ];
+        for reason in reasons {
+            let id = client.place_order(&customer, &1, &items, &notes);
+            client.cancel_order_with_reason(&admin, &id, &reason);
+            // Read the event before any other call replaces the buffer.
+            let (_, _, payload) = env
+                .events()
+                .all()
+                .iter()
+                .find(|(contract, t, _)| *contract == client.address && *t == topics)
+                .unwrap();
+            let (_seq, data): (u64, (u64, Address, CancelReason)) = payload.into_val(&env);
+            assert_eq!(data, (id, admin.clone(), reason.clone()));
+            assert_eq!(client.get_cancel_reason(&id), Some(reason));
+        }
+
+        // Plain cancellation records `Other`; open orders have no reason.
+        let plain = client.place_order(&customer, &1, &items, &notes);
+        assert_eq!(client.get_cancel_reason(&plain), None);
+        client.cancel_order(&customer, &plain);
+        assert_eq!(client.get_cancel_reason(&plain), Some(CancelReason::Other));
     }
 
     #[test]
-    fn test_customer_cancel_pending() {
+    fn test_grant_missed_reward_once() {
         let (env, client) = setup();
         let admin = Address::generate(&env);
         let customer = Address::generate(&env);
         client.initialize(&admin);
+        let token = setup_rewards(&env, &client, &admin, 100); // 1 %
+        client.set_reward_cooldown(&admin, &3_600);
+        let items = vec![&env, make_item(&env, 1, 1, 10_000_000)];
+        let notes = String::from_str(&env, "");
+
+        let first = client.place_order(&customer, &1, &items, &notes);
+        deliver(&client, &admin, first);
+        // Delivered inside the cooldown, so no reward.
+        let missed = client.place_order(&customer, &1, &items, &notes);
+        deliver(&client, &admin, missed);
+        assert_eq!(token.balance(&customer), 100_000);
+        assert!(client.try_grant_missed_reward(&admin, &first).is_err());
+
+        assert_eq!(client.grant_missed_reward(&admin, &missed), 100_000);
+        assert_eq!(token.balance(&customer), 200_000);
+        assert!(client.get_order(&missed).reward_minted);
+        assert!(client.try_grant_missed_reward(&admin, &missed).is_err());
+        assert_eq!(token.balance(&customer), 200_000);
+
+        let pending = client.place_order(&customer, &1, &items, &notes);
+        assert!(client.try_grant_missed_reward(&admin, &pending).is_err());
+    }
 
-        let items = vec![&env, make_item(&env, 2, 1, 3_000_000)];
+    #[test]
+    fn test_reward_minted_flag() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+        let items = vec![&env, make_item(&env, 1, 1, 10_000_000)];
+        let notes = String::from_str(&env, "");
+
+        // No loyalty token configured: nothing is minted.
+        let unrewarded = client.place_order(&customer, &1, &items, &notes);
+        deliver(&client, &admin, unrewarded);
+        assert!(!client.get_order(&unrewarded).reward_minted);
+
+        setup_rewards(&env, &client, &admin, 100); // 1 %
+        let rewarded = client.place_order(&customer, &1, &items, &notes);
+        assert!(!client.get_order(&rewarded).reward_minted);
+        deliver(&client, &admin, rewarded);
+        assert!(client.get_order(&rewarded).reward_minted);
+    }
+
+    #[test]
+    fn test_order_value_histogram() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+        let notes = String::from_str(&env, "");
+
+        for price in [5_000_000i128, 8_000_000, 15_000_000, 90_000_000] {
+            let items = vec![&env, make_item(&env, 1, 1, price)];
+            let id = client.place_order(&customer, &1, &items, &notes);
+            deliver(&client, &admin, id);
+        }
+        // Undelivered orders are not counted.
+        let items = vec![&env, make_item(&env, 1, 1, 5_000_000)];
+        client.place_order(&customer, &1, &items, &notes);
+
+        let histogram = client.get_order_value_histogram(&1, &10_000_000, &3);
+        assert_eq!(histogram, vec![&env, 2u64, 1, 1]);
+        assert!(client.try_get_order_value_histogram(&1, &0, &3).is_err());
+        assert!(client.try_get_order_value_histogram(&1, &10_000_000, &0).is_err());
+    }
+
+    #[test]
+    fn test_custom_transition_skips_preparing() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+        let items = vec![&env, make_item(&env, 1, 1, 5_000_000)];
         let id = client.place_order(&customer, &1, &items, &String::from_str(&env, ""));
+        client.advance_status(&admin, &id); // Confirmed
 
-        client.cancel_order(&customer, &id);
-        assert_eq!(client.get_order(&id).status, OrderStatus::Cancelled);
+        // Not in the table yet.
+        assert!(client.try_set_status(&admin, &id, &OrderStatus::Ready).is_err());
+
+        client.set_allowed_transition(&admin, &OrderStatus::Confirmed, &OrderStatus::Ready, &true);
+        assert!(client.is_transition_allowed(&OrderStatus::Confirmed, &OrderStatus::Ready));
+        client.set_status(&admin, &id, &OrderStatus::Ready);
+        assert_eq!(client.get_order(&id).status, OrderStatus::Ready);
+
+        // Other transitions stay closed.
+        assert!(client.try_set_status(&admin, &id, &OrderStatus::Pending).is_err());
     }
 
     #[test]
-    #[should_panic(expected = "customers may only cancel pending orders")]
-    fn test_customer_cannot_cancel_confirmed() {
+    fn test_set_status_cannot_enter_or_leave_delivered() {
         let (env, client) = setup();
         let admin = Address::generate(&env);
         let customer = Address::generate(&env);
         client.initialize(&admin);
+        let token = setup_rewards(&env, &client, &admin, 100); // 1 %
+        let items = vec![&env, make_item(&env, 1, 1, 10_000_000)];
+        let id = client.place_order(&customer, &1, &items, &String::from_str(&env, ""));
+        for _ in 0..3 {
+            client.advance_status(&admin, &id); // Ready
+        }
+
+        // Even when the table allows them, both moves would skip the
+        // reward, clawback and `completed` handling.
+        client.set_allowed_transition(&admin, &OrderStatus::Ready, &OrderStatus::Delivered, &true);
+        client.set_allowed_transition(
+            &admin,
+            &OrderStatus::Delivered,
+            &OrderStatus::Cancelled,
+            &true,
+        );
+        assert!(client.try_set_status(&admin, &id, &OrderStatus::Delivered).is_err());
+        assert_eq!(client.get_order(&id).status, OrderStatus::Ready);
+
+        client.advance_status(&admin, &id); // Delivered
+        assert_eq!(token.balance(&customer), 100_000);
+        assert!(client.try_set_status(&admin, &id, &OrderStatus::Cancelled).is_err());
+        assert_eq!(client.get_order(&id).status, OrderStatus::Delivered);
+        assert_eq!(token.balance(&customer), 100_000);
+    }
 
+    #[test]
+    fn test_actionable_orders_queue() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
         let items = vec![&env, make_item(&env, 1, 1, 5_000_000)];
-        let id = client.place_order(&customer, &1, &items, &String::from_str(&env, ""));
-        client.advance_status(&admin, &id);
-        client.cancel_order(&customer, &id);
+        let notes = String::from_str(&env, "");
+
+        // One order per status: Pending, Confirmed, Preparing, Ready, Delivered.
+        let mut ids: Vec<u64> = vec![&env];
+        for steps in 0..5u32 {
+            let id = client.place_order(&customer, &1, &items, &notes);
+            for _ in 0..steps {
+                client.advance_status(&admin, &id);
+            }
+            ids.push_back(id);
+        }
+        let cancelled = client.place_order(&customer, &1, &items, &notes);
+        client.cancel_order(&customer, &cancelled);
+
+        let expected = vec![&env, ids.get(0).unwrap(), ids.get(1).unwrap(), ids.get(2).unwrap()];
+        assert_eq!(client.get_actionable_orders(&1, &10), expected);
+        assert_eq!(client.get_actionable_orders(&1, &2).len(), 2);
+        assert_eq!(client.get_actionable_orders(&2, &10).len(), 0);
     }
 
     #[test]
-    fn test_get_restaurant_orders() {
+    fn test_reassign_order_to_another_branch() {
         let (env, client) = setup();
         let admin = Address::generate(&env);
         let customer = Address::generate(&env);
         client.initialize(&admin);
+        let items = vec![&env, make_item(&env, 1, 1, 5_000_000)];
+        let id = client.place_order(&customer, &1, &items, &String::from_str(&env, ""));
+        client.advance_status(&admin, &id); // Confirmed
+
+        client.reassign_order(&admin, &id, &2);
+        let topics = (Symbol::new(&env, "reassigned"), symbol_short!("order")).into_val(&env);
+        let (_, _, payload) = env
+            .events()
+            .all()
+            .iter()
+            .find(|(contract, t, _)| *contract == client.address && *t == topics)
+            .unwrap();
+        let (_seq, data): (u64, (u64, u64, u64)) = payload.into_val(&env);
+        assert_eq!(data, (id, 1, 2));
+
+        assert_eq!(client.get_order(&id).restaurant_id, 2);
+        assert_eq!(client.get_restaurant_orders(&1).len(), 0);
+        assert_eq!(client.get_restaurant_orders(&2), vec![&env, id]);
+        assert_eq!(client.get_restaurant_stats(&1).active_orders, 0);
+        assert_eq!(client.get_restaurant_stats(&2).active_orders, 1);
+        let stats = client.get_customer_stats(&customer);
+        assert_eq!(stats.favorite_restaurant, 2);
+        assert_eq!(stats.favorite_orders, 1);
+    }
 
+    #[test]
+    fn test_reassign_rejected_once_preparing() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
         let items = vec![&env, make_item(&env, 1, 1, 5_000_000)];
-        client.place_order(&customer, &7, &items.clone(), &String::from_str(&env, ""));
-        client.place_order(&customer, &7, &items, &String::from_str(&env, ""));
+        let id = client.place_order(&customer, &1, &items, &String::from_str(&env, ""));
+        client.advance_status(&admin, &id); // Confirmed
+        client.advance_status(&admin, &id); // Preparing
 
-        let orders = client.get_restaurant_orders(&7);
-        assert_eq!(orders.len(), 2);
+        assert!(client.try_reassign_order(&admin, &id, &2).is_err());
+        assert_eq!(client.get_order(&id).restaurant_id, 1);
+    }
+
+    #[test]
+    fn test_first_order_bonus_survives_delivery_before_token() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+        let items = vec![&env, make_item(&env, 1, 1, 10_000_000)];
+        let notes = String::from_str(&env, "");
+
+        // Delivered while rewards are not yet configured.
+        let early = client.place_order(&customer, &1, &items, &notes);
+        deliver(&client, &admin, early);
+
+        let token = setup_rewards(&env, &client, &admin, 100); // 1 %
+        client.set_first_order_bonus(&admin, &5_000_000);
+        let first = client.place_order(&customer, &1, &items, &notes);
+        deliver(&client, &admin, first);
+        assert_eq!(token.balance(&customer), 100_000 + 5_000_000);
+    }
+
+    #[test]
+    fn test_pending_rewards_tracks_accrual_and_claim() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+        setup_rewards(&env, &client, &admin, 100); // 1 %
+        client.set_claim_mode(&admin, &true);
+        assert_eq!(client.pending_rewards(&customer), 0);
+
+        let items = vec![&env, make_item(&env, 1, 1, 10_000_000)];
+        let notes = String::from_str(&env, "");
+        let first = client.place_order(&customer, &1, &items, &notes);
+        deliver(&client, &admin, first);
+        assert_eq!(client.pending_rewards(&customer), 100_000);
+        let second = client.place_order(&customer, &1, &items, &notes);
+        deliver(&client, &admin, second);
+        assert_eq!(client.pending_rewards(&customer), 200_000);
+
+        let pending = client.pending_rewards(&customer);
+        assert_eq!(client.claim_rewards(&customer), pending);
+        assert_eq!(client.pending_rewards(&customer), 0);
+    }
+
+    #[test]
+    fn test_preview_reward_nets_reward_debt() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        let friend = Address::generate(&env);
+        client.initialize(&admin);
+        let token = setup_rewards(&env, &client, &admin, 100); // 1 %
+        let items = vec![&env, make_item(&env, 1, 1, 10_000_000)];
+        let notes = String::from_str(&env, "");
+        let first = client.place_order(&customer, &1, &items, &notes);
+        deliver(&client, &admin, first);
+        token.transfer(&customer, &friend, &60_000);
+        client.flag_dispute(&customer, &first);
+        client.refund_dispute(&admin, &first);
+        assert_eq!(client.get_reward_debt(&customer), 60_000);
+
+        let second = client.place_order(&customer, &1, &items, &notes);
+        assert_eq!(client.preview_reward(&second), 40_000);
+        deliver(&client, &admin, second);
+        assert_eq!(token.balance(&customer), 40_000);
+    }
+
+    #[test]
+    fn test_reward_absorbed_by_debt_starts_cooldown() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        let friend = Address::generate(&env);
+        client.initialize(&admin);
+        let token = setup_rewards(&env, &client, &admin, 100); // 1 %
+        client.set_reward_cooldown(&admin, &3_600);
+        let items = vec![&env, make_item(&env, 1, 1, 10_000_000)];
+        let notes = String::from_str(&env, "");
+        let first = client.place_order(&customer, &1, &items, &notes);
+        deliver(&client, &admin, first);
+        token.transfer(&customer, &friend, &100_000);
+        client.flag_dispute(&customer, &first);
+        client.refund_dispute(&admin, &first);
+        assert_eq!(client.get_reward_debt(&customer), 100_000);
+
+        // The whole reward goes to the debt but still starts the cooldown.
+        env.ledger().with_mut(|l| l.timestamp += 3_600);
+        let second = client.place_order(&customer, &1, &items, &notes);
+        assert_eq!(client.preview_reward(&second), 0);
+        deliver(&client, &admin, second);
+        assert_eq!(client.get_reward_debt(&customer), 0);
+
+        env.ledger().with_mut(|l| l.timestamp += 600);
+        let third = client.place_order(&customer, &1, &items, &notes);
+        assert_eq!(client.preview_reward(&third), 0);
+        deliver(&client, &admin, third);
+        assert_eq!(token.balance(&customer), 0);
+    }
+
+    #[test]
+    fn test_failed_mint_keeps_delivery_and_can_be_granted() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        client.initialize(&admin);
+        let token = setup_rewards(&env, &client, &admin, 100); // 1 %
+        token.set_mint_rate_limit(&admin, &3_600, &1);
+        let items = vec![&env, make_item(&env, 1, 1, 10_000_000)];
+        let id = client.place_order(&customer, &1, &items, &String::from_str(&env, ""));
+
+        // The throttled mint fails, but the delivery still commits.
+        deliver(&client, &admin, id);
+        let order = client.get_order(&id);
+        assert_eq!(order.status, OrderStatus::Delivered);
+        assert!(!order.reward_minted);
+        assert_eq!(token.balance(&customer), 0);
+        assert_eq!(client.get_customer_stats(&customer).bite_earned, 0);
+
+        // Still throttled, so the grant fails too.
+        assert!(client.try_grant_missed_reward(&admin, &id).is_err());
+
+        token.set_mint_rate_limit(&admin, &0, &0);
+        assert_eq!(client.grant_missed_reward(&admin, &id), 100_000);
+        assert_eq!(token.balance(&customer), 100_000);
+        assert!(client.try_grant_missed_reward(&admin, &id).is_err());
+    }
+
+    #[test]
+    fn test_reward_absorbed_by_debt_is_settled() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let customer = Address::generate(&env);
+        let friend = Address::generate(&env);
+        client.initialize(&admin);
+        let token = setup_rewards(&env, &client, &admin, 100); // 1 %
+        let items = vec![&env, make_item(&env, 1, 1, 10_000_000)];
+        let notes = String::from_str(&env, "");
+        let first = client.place_order(&customer, &1, &items, &notes);
+        deliver(&client, &admin, first);
+        token.transfer(&customer, &friend, &100_000);
+        client.flag_dispute(&customer, &first);
+        client.refund_dispute(&admin, &first);
+        assert_eq!(client.get_reward_debt(&customer), 100_000);
+
+        let second = client.place_order(&customer, &1, &items, &notes);
+        deliver(&client, &admin, second);
+        assert_eq!(client.get_reward_debt(&customer), 0);
+        assert!(client.get_order(&second).reward_minted);
+
+        // The debt already consumed this reward; it cannot be granted again.
+        assert!(client.try_grant_missed_reward(&admin, &second).is_err());
+        assert_eq!(token.balance(&customer), 0);
     }
 }
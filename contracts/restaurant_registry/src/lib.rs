@@ -5,16 +5,43 @@
 //! used as a foreign key in the Order and Payment contracts.
 //!
 //! ## Roles
-//! - **Admin** – contract deployer; can deactivate any restaurant.
+//! - **Admin** – contract deployer; can deactivate any restaurant and, when
+//!   approval is required, approves newly registered ones.
 //! - **Owner** – the wallet that registered a restaurant; can update its
-//!   own restaurant metadata and toggle its active flag.
+//!   own restaurant metadata, toggle its active flag and burn BITE to
+//!   upgrade its tier.
+//!
+//! ## Onboarding approval
+//! When the admin turns on `set_approval_required`, new restaurants are
+//! registered inactive with `pending_approval` set and cannot be activated
+//! by their owner until the admin calls `approve_restaurant`.
 
 #![no_std]
 
 use soroban_sdk::{
-    contract, contractimpl, contracttype, symbol_short, Address, Env, String,
+    contract, contractclient, contractimpl, contracttype, symbol_short, Address, Env, String,
+    Vec,
 };
 
+/// Upper bound on the number of restaurants returned by a single page.
+const MAX_PAGE_SIZE: u32 = 50;
+
+/// Upper bound on the number of registry entries inspected by a scan.
+const MAX_SCAN: u64 = 500;
+
+/// Upper bound on the number of restaurants toggled by `set_active_batch`.
+const MAX_BATCH_SIZE: u32 = 50;
+
+// ---------------------------------------------------------------------------
+// External contracts
+// ---------------------------------------------------------------------------
+
+/// The subset of the BITE loyalty token used to pay for tier upgrades.
+#[contractclient(name = "LoyaltyClient")]
+pub trait LoyaltyTokenInterface {
+    fn burn(env: Env, from: Address, amount: i128);
+}
+
 // ---------------------------------------------------------------------------
 // Storage types
 // ---------------------------------------------------------------------------
@@ -35,6 +62,10 @@ pub struct Restaurant {
     pub is_active: bool,
     /// Ledger timestamp of registration.
     pub created_at: u64,
+    /// Feature tier unlocked by burning BITE (0 = basic).
+    pub tier: u32,
+    /// Registered while approval was required and not yet approved.
+    pub pending_approval: bool,
 }
 
 /// Storage key discriminants.
@@ -44,10 +75,18 @@ pub enum DataKey {
     Admin,
     /// Singleton: total number of registered restaurants.
     Count,
+    /// Singleton: number of restaurants currently marked active.
+    ActiveCount,
     /// Per-restaurant data keyed by numeric ID.
     Restaurant(u64),
     /// Reverse lookup: owner address → restaurant ID.
     OwnerToId(Address),
+    /// Singleton: BITE token burned for tier upgrades.
+    LoyaltyToken,
+    /// BITE cost of upgrading to a tier.
+    TierCost(u32),
+    /// Singleton: whether new restaurants need admin approval.
+    ApprovalRequired,
 }
 
 // ---------------------------------------------------------------------------
@@ -73,9 +112,18 @@ impl RestaurantRegistry {
         }
         env.storage().instance().set(&DataKey::Admin, &admin);
         env.storage().instance().set(&DataKey::Count, &0u64);
+        env.storage().instance().set(&DataKey::ActiveCount, &0u64);
         env.storage().instance().extend_ttl(17_280, 17_280); // ~1 day
     }
 
+    /// Whether `initialize` has already been called.
+    ///
+    /// Deployment scripts can check this instead of relying on the
+    /// "already initialized" panic.
+    pub fn is_initialized(env: Env) -> bool {
+        env.storage().instance().has(&DataKey::Admin)
+    }
+
     // -----------------------------------------------------------------------
     // Writes
     // -----------------------------------------------------------------------
@@ -85,6 +133,9 @@ impl RestaurantRegistry {
     /// # Returns
     /// The newly assigned restaurant ID (starts at 1).
     ///
+    /// If approval is required the restaurant starts inactive and pending
+    /// until `approve_restaurant` is called.
+    ///
     /// # Panics
     /// - If the owner already has a registered restaurant.
     pub fn register_restaurant(env: Env, owner: Address, name: String, slug: String) -> u64 {
@@ -104,14 +155,17 @@ impl RestaurantRegistry {
             .get(&DataKey::Count)
             .unwrap_or(0);
         let id: u64 = count + 1;
+        let pending = Self::approval_required(env.clone());
 
         let restaurant = Restaurant {
             id,
             owner: owner.clone(),
             name: name.clone(),
             slug: slug.clone(),
-            is_active: true,
+            is_active: !pending,
             created_at: env.ledger().timestamp(),
+            tier: 0,
+            pending_approval: pending,
         };
 
         let ttl: u32 = 2_073_600; // ~120 days on Stellar
@@ -130,6 +184,12 @@ impl RestaurantRegistry {
             .extend_ttl(&DataKey::OwnerToId(owner.clone()), ttl, ttl);
 
         env.storage().instance().set(&DataKey::Count, &id);
+        if !pending {
+            let active_count = Self::get_active_count(env.clone());
+            env.storage()
+                .instance()
+                .set(&DataKey::ActiveCount, &(active_count + 1));
+        }
         env.storage().instance().extend_ttl(17_280, 17_280);
 
         // Emit: (topic1, topic2) -> (id, owner)
@@ -184,11 +244,12 @@ impl RestaurantRegistry {
 
     /// Activate or deactivate a restaurant.
     ///
-    /// Only the owner or admin may change the active flag.
+    /// Only the owner or admin may change the active flag.  A restaurant
+    /// pending approval cannot be activated; see `approve_restaurant`.
     pub fn set_active(env: Env, caller: Address, restaurant_id: u64, active: bool) {
         caller.require_auth();
 
-        let mut restaurant: Restaurant = env
+        let restaurant: Restaurant = env
             .storage()
             .persistent()
             .get(&DataKey::Restaurant(restaurant_id))
@@ -198,8 +259,76 @@ impl RestaurantRegistry {
         if caller != restaurant.owner && caller != admin {
             panic!("unauthorized");
         }
+        if active && restaurant.pending_approval {
+            panic!("restaurant pending approval");
+        }
 
-        restaurant.is_active = active;
+        Self::apply_active(&env, restaurant, active);
+    }
+
+    /// Activate or deactivate many restaurants at once (admin only), e.g.
+    /// during a platform incident.
+    ///
+    /// IDs that do not exist, or that are pending approval when activating,
+    /// are skipped and returned.  At most `MAX_BATCH_SIZE` IDs per call.
+    pub fn set_active_batch(
+        env: Env,
+        admin: Address,
+        restaurant_ids: Vec<u64>,
+        active: bool,
+    ) -> Vec<u64> {
+        admin.require_auth();
+        if admin != Self::admin(env.clone()) {
+            panic!("unauthorized");
+        }
+        if restaurant_ids.len() > MAX_BATCH_SIZE {
+            panic!("batch too large");
+        }
+
+        let mut skipped: Vec<u64> = Vec::new(&env);
+        for id in restaurant_ids.iter() {
+            let restaurant: Option<Restaurant> =
+                env.storage().persistent().get(&DataKey::Restaurant(id));
+            match restaurant {
+                Some(r) if !(active && r.pending_approval) => Self::apply_active(&env, r, active),
+                _ => skipped.push_back(id),
+            }
+        }
+        skipped
+    }
+
+    /// Burn BITE from the owner to move a restaurant up to `tier`.
+    ///
+    /// The cost is the admin-configured `TierCost` for the target tier.
+    ///
+    /// # Panics
+    /// - If the caller is not the restaurant's owner.
+    /// - If `tier` is not above the current tier or has no configured cost.
+    pub fn upgrade_tier(env: Env, owner: Address, restaurant_id: u64, tier: u32) {
+        owner.require_auth();
+
+        let mut restaurant = Self::get_restaurant(env.clone(), restaurant_id);
+        if owner != restaurant.owner {
+            panic!("unauthorized");
+        }
+        if tier <= restaurant.tier {
+            panic!("tier must increase");
+        }
+        let cost: i128 = env
+            .storage()
+            .instance()
+            .get(&DataKey::TierCost(tier))
+            .unwrap_or_else(|| panic!("tier not available"));
+        let token: Address = env
+            .storage()
+            .instance()
+            .get(&DataKey::LoyaltyToken)
+            .unwrap_or_else(|| panic!("loyalty token not set"));
+
+        if cost > 0 {
+            LoyaltyClient::new(&env, &token).burn(&owner, &cost);
+        }
+        restaurant.tier = tier;
 
         let ttl: u32 = 2_073_600;
         env.storage()
@@ -210,11 +339,86 @@ impl RestaurantRegistry {
             .extend_ttl(&DataKey::Restaurant(restaurant_id), ttl, ttl);
 
         env.events().publish(
-            (symbol_short!("setactive"), symbol_short!("rest")),
-            (restaurant_id, active),
+            (symbol_short!("upgrade"), symbol_short!("rest")),
+            (restaurant_id, tier, cost),
         );
     }
 
+    // -----------------------------------------------------------------------
+    // Admin
+    // -----------------------------------------------------------------------
+
+    /// Approve a restaurant registered while approval was required and
+    /// activate it (admin only).
+    pub fn approve_restaurant(env: Env, admin: Address, restaurant_id: u64) {
+        admin.require_auth();
+        if admin != Self::admin(env.clone()) {
+            panic!("unauthorized");
+        }
+
+        let mut restaurant = Self::get_restaurant(env.clone(), restaurant_id);
+        if !restaurant.pending_approval {
+            panic!("restaurant not pending approval");
+        }
+        restaurant.pending_approval = false;
+        restaurant.is_active = true;
+
+        let ttl: u32 = 2_073_600;
+        env.storage()
+            .persistent()
+            .set(&DataKey::Restaurant(restaurant_id), &restaurant);
+        env.storage()
+            .persistent()
+            .extend_ttl(&DataKey::Restaurant(restaurant_id), ttl, ttl);
+
+        let active_count = Self::get_active_count(env.clone());
+        env.storage()
+            .instance()
+            .set(&DataKey::ActiveCount, &(active_count + 1));
+        env.storage().instance().extend_ttl(17_280, 17_280);
+
+        env.events().publish(
+            (symbol_short!("approve"), symbol_short!("rest")),
+            restaurant_id,
+        );
+    }
+
+    /// Require admin approval for restaurants registered from now on
+    /// (admin only).  Existing restaurants are unaffected.
+    pub fn set_approval_required(env: Env, caller: Address, required: bool) {
+        caller.require_auth();
+        if caller != Self::admin(env.clone()) {
+            panic!("unauthorized");
+        }
+        env.storage()
+            .instance()
+            .set(&DataKey::ApprovalRequired, &required);
+        env.storage().instance().extend_ttl(17_280, 17_280);
+    }
+
+    /// Set the BITE token burned by `upgrade_tier` (admin only).
+    pub fn set_loyalty_token(env: Env, caller: Address, token: Address) {
+        caller.require_auth();
+        if caller != Self::admin(env.clone()) {
+            panic!("unauthorized");
+        }
+        env.storage().instance().set(&DataKey::LoyaltyToken, &token);
+        env.storage().instance().extend_ttl(17_280, 17_280);
+    }
+
+    /// Set the BITE cost of upgrading to `tier` (admin only).
+    pub fn set_tier_cost(env: Env, caller: Address, tier: u32, cost: i128) {
+        caller.require_auth();
+        if caller != Self::admin(env.clone()) {
+            panic!("unauthorized");
+        }
+        if cost < 0 {
+            panic!("cost cannot be negative");
+        }
+        env.storage().instance().set(&DataKey::TierCost(tier), &cost);
+        env.storage().instance().extend_ttl(17_280, 17_280);
+    }
+
     // -----------------------------------------------------------------------
     // Reads (view)
     // -----------------------------------------------------------------------
@@ -227,6 +431,28 @@ impl RestaurantRegistry {
             .unwrap_or_else(|| panic!("restaurant not found"))
     }
 
+    /// Whether the restaurant exists, is approved and is active.
+    ///
+    /// The Order contract calls this before accepting a new order.
+    pub fn is_accepting_orders(env: Env, restaurant_id: u64) -> bool {
+        let restaurant: Option<Restaurant> = env
+            .storage()
+            .persistent()
+            .get(&DataKey::Restaurant(restaurant_id));
+        match restaurant {
+            Some(r) => r.is_active && !r.pending_approval,
+            None => false,
+        }
+    }
+
+    /// Whether newly registered restaurants need admin approval.
+    pub fn approval_required(env: Env) -> bool {
+        env.storage()
+            .instance()
+            .get(&DataKey::ApprovalRequired)
+            .unwrap_or(false)
+    }
+
     /// Return the restaurant ID owned by `owner`.
     pub fn get_owner_restaurant(env: Env, owner: Address) -> u64 {
         env.storage()
@@ -240,10 +466,84 @@ impl RestaurantRegistry {
         env.storage().instance().get(&DataKey::Count).unwrap_or(0)
     }
 
+    /// Number of restaurants currently accepting orders.
+    ///
+    /// Following the `get_active_restaurants` cursor to the end yields
+    /// exactly this many restaurants.
+    pub fn get_active_count(env: Env) -> u64 {
+        env.storage()
+            .instance()
+            .get(&DataKey::ActiveCount)
+            .unwrap_or(0)
+    }
+
+    /// Return a page of active restaurants in registration order, starting
+    /// at ID `start_id`.
+    ///
+    /// At most `MAX_PAGE_SIZE` entries are returned and at most `MAX_SCAN`
+    /// IDs inspected per call, so a page may come back short.  Returns
+    /// `(page, next)`: pass `next` as `start_id` to continue, until it is
+    /// `0` once the whole registry has been scanned.
+    pub fn get_active_restaurants(env: Env, start_id: u64, limit: u32) -> (Vec<Restaurant>, u64) {
+        let limit = limit.min(MAX_PAGE_SIZE);
+        let mut page: Vec<Restaurant> = Vec::new(&env);
+
+        let count = Self::get_count(env.clone());
+        let mut id = start_id.max(1);
+        let end = id.saturating_add(MAX_SCAN - 1).min(count);
+        while id <= end && page.len() < limit {
+            let restaurant: Option<Restaurant> =
+                env.storage().persistent().get(&DataKey::Restaurant(id));
+            if let Some(restaurant) = restaurant {
+                if restaurant.is_active {
+                    page.push_back(restaurant);
+                }
+            }
+            id += 1;
+        }
+        let next = if id > count { 0 } else { id };
+        (page, next)
+    }
+
     /// Return the admin address.
     pub fn admin(env: Env) -> Address {
         env.storage().instance().get(&DataKey::Admin).unwrap()
     }
+
+    // -----------------------------------------------------------------------
+    // Internal helpers
+    // -----------------------------------------------------------------------
+
+    /// Store the new active flag, keep `ActiveCount` in step and emit
+    /// `setactive`.
+    fn apply_active(env: &Env, mut restaurant: Restaurant, active: bool) {
+        if restaurant.is_active != active {
+            let active_count = Self::get_active_count(env.clone());
+            let active_count = if active {
+                active_count + 1
+            } else {
+                active_count.saturating_sub(1)
+            };
+            env.storage()
+                .instance()
+                .set(&DataKey::ActiveCount, &active_count);
+            env.storage().instance().extend_ttl(17_280, 17_280);
+        }
+        restaurant.is_active = active;
+
+        let ttl: u32 = 2_073_600;
+        env.storage()
+            .persistent()
+            .set(&DataKey::Restaurant(restaurant.id), &restaurant);
+        env.storage()
+            .persistent()
+            .extend_ttl(&DataKey::Restaurant(restaurant.id), ttl, ttl);
+
+        env.events().publish(
+            (symbol_short!("setactive"), symbol_short!("rest")),
+            (restaurant.id, active),
+        );
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -253,7 +553,8 @@ impl RestaurantRegistry {
 #[cfg(test)]
 mod test {
     use super::*;
-    use soroban_sdk::testutils::{Address as _, Ledger};
+    use loyalty_token::{LoyaltyToken, LoyaltyTokenClient};
+    use soroban_sdk::testutils::{Address as _, Events};
     use soroban_sdk::Env;
 
     fn setup() -> (Env, RestaurantRegistryClient<'static>) {
@@ -354,4 +655,136 @@ mod test {
             &String::from_str(&env, "second"),
         );
     }
+
+    #[test]
+    fn test_get_active_restaurants() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        client.initialize(&admin);
+
+        let mut ids = [0u64; 3];
+        for id in ids.iter_mut() {
+            let owner = Address::generate(&env);
+            *id = client.register_restaurant(
+                &owner,
+                &String::from_str(&env, "Rest"),
+                &String::from_str(&env, "rest"),
+            );
+        }
+        assert_eq!(client.get_active_count(), 3);
+        let (active, next) = client.get_active_restaurants(&0, &10);
+        assert_eq!(active.len(), 3);
+        assert_eq!(next, 0);
+
+        client.set_active(&admin, &ids[1], &false);
+        assert_eq!(client.get_active_count(), 2);
+
+        let (active, _) = client.get_active_restaurants(&0, &10);
+        assert_eq!(active.len(), 2);
+        assert_eq!(active.get(0).unwrap().id, ids[0]);
+        assert_eq!(active.get(1).unwrap().id, ids[2]);
+
+        // Following the cursor skips over the inactive restaurant.
+        let (page, next) = client.get_active_restaurants(&0, &1);
+        assert_eq!(page.get(0).unwrap().id, ids[0]);
+        assert_eq!(next, ids[1]);
+        let (page, next) = client.get_active_restaurants(&next, &1);
+        assert_eq!(page.len(), 1);
+        assert_eq!(page.get(0).unwrap().id, ids[2]);
+        assert_eq!(next, 0);
+    }
+
+    #[test]
+    fn test_is_initialized() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+
+        assert!(!client.is_initialized());
+        client.initialize(&admin);
+        assert!(client.is_initialized());
+    }
+
+    #[test]
+    fn test_upgrade_tier_burns_bite() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let owner = Address::generate(&env);
+        client.initialize(&admin);
+
+        let token_id = env.register_contract(None, LoyaltyToken);
+        let token = LoyaltyTokenClient::new(&env, &token_id);
+        token.initialize(&admin, &admin);
+        token.mint(&admin, &owner, &5_000_000);
+        client.set_loyalty_token(&admin, &token_id);
+        client.set_tier_cost(&admin, &1, &2_000_000);
+
+        let id = client.register_restaurant(
+            &owner,
+            &String::from_str(&env, "Verified Eats"),
+            &String::from_str(&env, "verified-eats"),
+        );
+        client.upgrade_tier(&owner, &id, &1);
+
+        assert_eq!(client.get_restaurant(&id).tier, 1);
+        assert_eq!(token.balance(&owner), 3_000_000);
+        assert_eq!(token.total_supply(), 3_000_000);
+    }
+
+    #[test]
+    fn test_approval_gate() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        let owner = Address::generate(&env);
+        client.initialize(&admin);
+        client.set_approval_required(&admin, &true);
+
+        let id = client.register_restaurant(
+            &owner,
+            &String::from_str(&env, "Test Rest"),
+            &String::from_str(&env, "test-rest"),
+        );
+        let rest = client.get_restaurant(&id);
+        assert!(rest.pending_approval && !rest.is_active);
+        assert!(!client.is_accepting_orders(&id));
+        assert_eq!(client.get_active_count(), 0);
+        assert!(client.try_set_active(&owner, &id, &true).is_err());
+        assert!(client.try_approve_restaurant(&owner, &id).is_err());
+
+        client.approve_restaurant(&admin, &id);
+        let rest = client.get_restaurant(&id);
+        assert!(!rest.pending_approval && rest.is_active);
+        assert!(client.is_accepting_orders(&id));
+        assert_eq!(client.get_active_count(), 1);
+        assert!(client.try_approve_restaurant(&admin, &id).is_err());
+    }
+
+    #[test]
+    fn test_set_active_batch() {
+        let (env, client) = setup();
+        let admin = Address::generate(&env);
+        client.initialize(&admin);
+        let mut ids = Vec::new(&env);
+        for _ in 0..3 {
+            let id = client.register_restaurant(
+                &Address::generate(&env),
+                &String::from_str(&env, "Test Rest"),
+                &String::from_str(&env, "test-rest"),
+            );
+            ids.push_back(id);
+        }
+        assert_eq!(client.get_active_count(), 3);
+        ids.push_back(99);
+
+        let skipped = client.set_active_batch(&admin, &ids, &false);
+        // One event per restaurant toggled; the unknown ID emits nothing.
+        assert_eq!(env.events().all().len(), 3);
+        assert_eq!(skipped, Vec::from_array(&env, [99u64]));
+        assert_eq!(client.get_active_count(), 0);
+        for id in 1..=3u64 {
+            assert!(!client.get_restaurant(&id).is_active);
+        }
+
+        let owner = client.get_restaurant(&1).owner;
+        assert!(client.try_set_active_batch(&owner, &ids, &true).is_err());
+    }
 }